@@ -1,9 +1,10 @@
 use bevy::prelude::*;
 
-use crate::{editor::EditorOptions, GameStates};
+use crate::{editor::EditorOptions, stage::StageFile, GameStates};
 
 const ZOOMING_IN_CAMERA_LERP_SPEED: f32 = 0.01;
 const ZOOMING_OUT_CAMERA_LERP_SPEED: f32 = 0.8;
+const STAGE_EDITING_CAMERA_MARGIN: f32 = 1.1;
 
 pub struct CameraPlugin;
 
@@ -36,6 +37,7 @@ fn camera_follows(
     character_query: Query<(&Transform, &CameraFollows), Without<MainGameCamera>>,
     window: Query<&Window>,
     options: Res<EditorOptions>,
+    stage: Res<StageFile>,
 ) {
     let (mut camera_tf, mut camera_projection) = camera_query.single_mut();
     let window = window.single();
@@ -83,6 +85,23 @@ fn camera_follows(
             } else {
                 ZOOMING_OUT_CAMERA_LERP_SPEED
             };
+
+    // Keep the view inside the stage's camera bounds, centered if it's bigger than them
+    let half_view = Vec2::new(window_width, window_height) * camera_projection.scale / 2.;
+    let bounds = stage.camera_bounds;
+    let min = bounds.min + half_view;
+    let max = bounds.max - half_view;
+    let center = bounds.center();
+    camera_tf.translation.x = if min.x < max.x {
+        camera_tf.translation.x.clamp(min.x, max.x)
+    } else {
+        center.x
+    };
+    camera_tf.translation.y = if min.y < max.y {
+        camera_tf.translation.y.clamp(min.y, max.y)
+    } else {
+        center.y
+    };
 }
 
 fn camera_editing(
@@ -90,16 +109,26 @@ fn camera_editing(
     character_query: Query<(&Transform, &CameraFollows), Without<MainGameCamera>>,
     window: Query<&Window>,
     options: Res<EditorOptions>,
+    stage: Res<StageFile>,
 ) {
-    if options.editing_character.is_none() {
-        return; //TODO
-    }
-
     let (mut camera_tf, mut camera_projection) = camera_query.single_mut();
     let window = window.single();
     let window_height = window.height();
     let window_width = window.width();
 
+    // The whole blast zone is shown while editing the stage, with some room around it
+    if options.editing_stage {
+        let zone = stage.blast_zone;
+        camera_tf.translation = zone.center().extend(camera_tf.translation.z);
+        camera_projection.scale = (zone.width() / window_width).max(zone.height() / window_height)
+            * STAGE_EDITING_CAMERA_MARGIN;
+        return;
+    }
+
+    if options.editing_character.is_none() {
+        return; //TODO
+    }
+
     let (tf, camera_follows) = character_query
         .get(options.editing_character.unwrap())
         .unwrap();
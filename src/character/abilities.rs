@@ -0,0 +1,211 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{CharacterMovement, Helpless, LedgeHang};
+use crate::stage::StageFile;
+
+/// Ways of moving only some characters have.
+/// Every ability is optional, characters without any move like everyone else
+#[derive(Serialize, Deserialize, Component, Debug, Clone, Default)]
+#[serde(default)]
+pub struct MovementAbilities {
+    pub float: Option<Float>,
+    pub glide: Option<Glide>,
+    pub multi_jump: Option<MultiJump>,
+    pub wall_cling: Option<WallCling>,
+    pub crawl: Option<Crawl>,
+
+    /// How long the character can still float for before touching the stage
+    #[serde(skip)]
+    float_time_left: f32,
+
+    /// How long the character can still cling to walls for before touching the stage
+    #[serde(skip)]
+    cling_time_left: f32,
+
+    #[serde(skip)]
+    is_clinging: bool,
+}
+
+/// Hover at a constant height while holding jump
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Float {
+    /// How long the character can float for each time it leaves the stage, in seconds
+    pub duration: f32,
+}
+
+/// Fall slowly while holding jump once all the air jumps are used
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Glide {
+    pub fall_speed: f32,
+    pub speed: f32,
+}
+
+/// Extra air jumps, each one weaker than the last
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MultiJump {
+    /// Replaces `max_air_jumps`
+    pub jumps: usize,
+    /// Each air jump is this times the height of the previous one
+    pub decay: f32,
+}
+
+/// Stick to walls by holding towards them, then slowly slide down
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WallCling {
+    /// How long the character can hold onto walls for each time it leaves the stage, in seconds
+    pub duration: f32,
+    pub slide_speed: f32,
+}
+
+/// Move slowly instead of stopping while holding down on the stage
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Crawl {
+    pub speed: f32,
+}
+
+impl Default for Float {
+    fn default() -> Self {
+        Self { duration: 1. }
+    }
+}
+
+impl Default for Glide {
+    fn default() -> Self {
+        Self {
+            fall_speed: 100.,
+            speed: 500.,
+        }
+    }
+}
+
+impl Default for MultiJump {
+    fn default() -> Self {
+        Self {
+            jumps: 4,
+            decay: 0.8,
+        }
+    }
+}
+
+impl Default for WallCling {
+    fn default() -> Self {
+        Self {
+            duration: 1.,
+            slide_speed: 100.,
+        }
+    }
+}
+
+impl Default for Crawl {
+    fn default() -> Self {
+        Self { speed: 150. }
+    }
+}
+
+impl MovementAbilities {
+    pub fn max_air_jumps(&self, movement: &CharacterMovement) -> usize {
+        self.multi_jump
+            .as_ref()
+            .map_or(movement.max_air_jumps, |multi_jump| multi_jump.jumps)
+    }
+
+    /// How strong the next air jump is
+    pub fn air_jump_boost(&self, movement: &CharacterMovement) -> f32 {
+        let Some(multi_jump) = &self.multi_jump else {
+            return movement.jump_boost;
+        };
+
+        let air_jumps_used = multi_jump.jumps.saturating_sub(movement.current_air_jumps);
+        movement.jump_boost * multi_jump.decay.powi(air_jumps_used as i32)
+    }
+
+    /// The speed on the stage while holding down, if it isn't the usual one
+    pub fn crawl_speed(&self) -> Option<f32> {
+        self.crawl.as_ref().map(|crawl| crawl.speed)
+    }
+
+    /// With wall cling, walljumps only happen when jumping off a wall the character is holding
+    pub fn is_clinging(&self) -> bool {
+        self.is_clinging
+    }
+
+    fn refresh(&mut self) {
+        self.float_time_left = self.float.as_ref().map_or(0., |float| float.duration);
+        self.cling_time_left = self
+            .wall_cling
+            .as_ref()
+            .map_or(0., |wall_cling| wall_cling.duration);
+    }
+}
+
+/// Applies float, glide and wall cling.
+/// Multi jump and crawl change `character_movement` itself
+pub fn movement_abilities(
+    mut character_query: Query<(
+        &mut MovementAbilities,
+        &mut CharacterMovement,
+        &mut Velocity,
+        Option<&LedgeHang>,
+        Option<&Helpless>,
+    )>,
+    stage: Res<StageFile>,
+    time: Res<Time>,
+) {
+    let scale = stage.physics.pixels_per_unit;
+
+    for (mut abilities, mut movement, mut vel, hang, helpless) in character_query.iter_mut() {
+        let abilities = &mut *abilities;
+        let holding_jump = movement.is_holding_jump;
+
+        // Reset the variables
+        movement.is_holding_jump = false;
+        abilities.is_clinging = false;
+
+        if movement.is_on_stage() {
+            abilities.refresh();
+            continue;
+        }
+
+        if hang.is_some() || helpless.is_some() {
+            continue;
+        }
+
+        // Wall cling
+        let holding_towards_wall =
+            movement.is_on_stage_wall() && movement.stage_touch_force.x * movement.x > 0.;
+
+        if let Some(wall_cling) = &abilities.wall_cling {
+            if holding_towards_wall {
+                if abilities.cling_time_left > 0. {
+                    vel.linvel.y = 0.;
+                    abilities.cling_time_left -= time.delta_seconds();
+                } else {
+                    vel.linvel.y = vel.linvel.y.max(-wall_cling.slide_speed * scale);
+                }
+                abilities.is_clinging = true;
+                continue;
+            }
+        }
+
+        if !holding_jump || vel.linvel.y > 0. {
+            continue;
+        }
+
+        // Float
+        if abilities.float.is_some() && abilities.float_time_left > 0. {
+            vel.linvel.y = 0.;
+            abilities.float_time_left -= time.delta_seconds();
+            continue;
+        }
+
+        // Glide
+        if let Some(glide) = &abilities.glide {
+            if movement.current_air_jumps == 0 {
+                vel.linvel.y = vel.linvel.y.max(-glide.fall_speed * scale);
+                vel.linvel.x = movement.facing * glide.speed * scale;
+            }
+        }
+    }
+}
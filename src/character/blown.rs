@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::hazard;
+
+pub struct BlownPlugin;
+
+impl Plugin for BlownPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(remove_blown_velocity.before(super::character_movement))
+            .add_system(
+                apply_blown_velocity
+                    .after(super::character_movement)
+                    .after(super::abilities::movement_abilities)
+                    .after(hazard::wind),
+            );
+    }
+}
+
+/// The part of the character's velocity that comes from the wind it's in.
+/// Like `Launch`, it's kept apart so the character's own movement works as usual
+#[derive(Component, Debug, Clone, Default)]
+pub struct Blown {
+    /// How fast the winds are pushing the character this frame, gone once it's applied
+    pub velocity: Vec2,
+
+    applied: Vec2,
+}
+
+/// Takes last frame's wind out, so `character_movement` only sees the character's own
+fn remove_blown_velocity(mut character_query: Query<(&mut Velocity, &mut Blown)>) {
+    for (mut vel, mut blown) in character_query.iter_mut() {
+        vel.linvel -= blown.applied;
+        blown.applied = Vec2::ZERO;
+    }
+}
+
+/// Puts this frame's wind into the velocity
+fn apply_blown_velocity(mut character_query: Query<(&mut Velocity, &mut Blown)>) {
+    for (mut vel, mut blown) in character_query.iter_mut() {
+        vel.linvel += blown.velocity;
+        blown.applied = blown.velocity;
+        blown.velocity = Vec2::ZERO;
+    }
+}
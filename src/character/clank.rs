@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::{
+    moves::HitboxKind, projectile::Projectile, CharacterAttack, CharacterAttackController,
+};
+
+/// Hitboxes whose damage is at most this far apart clank with each other.
+/// Past it, the stronger one wins and keeps going
+const CLANK_DAMAGE_THRESHOLD: f32 = 9.;
+/// How long a character can't act for after clanking, at 0 damage
+const REBOUND_BASE_DURATION: f32 = 0.25;
+/// How much longer the rebound is for each point of damage of the hitbox that clanked
+const REBOUND_DURATION_PER_DAMAGE: f32 = 0.01;
+/// The speed at which clanking pushes characters away from each other
+const CLANK_PUSHBACK_SPEED: f32 = 200.;
+
+/// While present, the character can't move or attack, after its attack clanked
+#[derive(Component, Debug, Clone)]
+pub struct Rebound(pub Timer);
+
+impl Rebound {
+    fn new(damage: f32) -> Self {
+        Self(Timer::from_seconds(
+            REBOUND_BASE_DURATION + damage * REBOUND_DURATION_PER_DAMAGE,
+            TimerMode::Once,
+        ))
+    }
+}
+
+/// Hitboxes hitting each other. Grounded attacks clank, unless one is much stronger,
+/// aerials and transcendent hitboxes go through and both hit
+pub fn clank_system(
+    mut hitbox_query: Query<(&mut CharacterAttack, &Parent), Without<Projectile>>,
+    mut character_query: Query<(&Transform, &mut CharacterAttackController)>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut commands: Commands,
+) {
+    for collision in collision_events.iter() {
+        let CollisionEvent::Started(col1, col2, _) = collision else {
+            continue;
+        };
+
+        let Ok([(mut attack1, owner1), (mut attack2, owner2)]) =
+            hitbox_query.get_many_mut([*col1, *col2])
+        else {
+            continue;
+        };
+        let (owner1, owner2) = (owner1.get(), owner2.get());
+
+        if owner1 == owner2
+            || attack1.kind != HitboxKind::Attack
+            || attack2.kind != HitboxKind::Attack
+            || attack1.transcendent
+            || attack2.transcendent
+        {
+            continue;
+        }
+
+        let Ok([(tf1, mut controller1), (tf2, mut controller2)]) =
+            character_query.get_many_mut([owner1, owner2])
+        else {
+            continue;
+        };
+
+        // Aerials trade
+        if controller1.is_aerial_attack || controller2.is_aerial_attack {
+            continue;
+        }
+
+        let difference = attack1.damage - attack2.damage;
+        let away_from_2 = (tf1.translation.x - tf2.translation.x).signum();
+
+        if difference <= CLANK_DAMAGE_THRESHOLD {
+            attack1.clanked = true;
+            controller1.velocity_from_knockback +=
+                Vec2::new(away_from_2 * CLANK_PUSHBACK_SPEED, 0.);
+            commands.entity(owner1).insert(Rebound::new(attack1.damage));
+        }
+        if difference >= -CLANK_DAMAGE_THRESHOLD {
+            attack2.clanked = true;
+            controller2.velocity_from_knockback +=
+                Vec2::new(-away_from_2 * CLANK_PUSHBACK_SPEED, 0.);
+            commands.entity(owner2).insert(Rebound::new(attack2.damage));
+        }
+    }
+}
+
+pub fn rebound_system(
+    mut rebound_query: Query<(Entity, &mut Rebound)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut rebound) in rebound_query.iter_mut() {
+        if rebound.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Rebound>();
+        }
+    }
+}
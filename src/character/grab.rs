@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ruleset::{Handicap, Ruleset};
+
+use super::{
+    launch::{self, Launch},
+    moves::{AttackKind, Moveset},
+    Character, CharacterAttackController, CharacterMovement, Hitstun,
+};
+
+/// How much each button press fills the grab escape meter
+const GRAB_MASH_ESCAPE: f32 = 0.15;
+/// How full the escape meter needs to be at 0%
+const GRAB_ESCAPE_BASE: f32 = 1.;
+/// How much fuller the escape meter needs to be for each percent of damage
+const GRAB_ESCAPE_PER_PERCENT: f32 = 0.02;
+/// How fast the grabbed character gets pushed away when escaping
+const GRAB_ESCAPE_SPEED: f32 = 400.;
+/// How far the stick has to be held to pick a throw
+const THROW_THRESHOLD: f32 = 0.5;
+
+/// Where grabbed characters are held, relative to the grabber facing right
+#[derive(Serialize, Deserialize, Component, Debug, Clone)]
+pub struct GrabHand {
+    pub offset: Vec2,
+}
+
+/// Present on a character holding someone
+#[derive(Component, Debug, Clone)]
+pub struct Grabbing {
+    pub victim: Entity,
+}
+
+/// Present on a character being held
+#[derive(Component, Debug, Clone)]
+pub struct Grabbed {
+    pub grabber: Entity,
+
+    /// Fills up over time and by mashing, the character escapes once it is full
+    escape_meter: f32,
+}
+
+impl Default for GrabHand {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::new(60., 0.),
+        }
+    }
+}
+
+impl Grabbed {
+    pub fn new(grabber: Entity) -> Self {
+        Self {
+            grabber,
+            escape_meter: 0.,
+        }
+    }
+}
+
+/// Holds grabbed characters in the grabber's hand, picks pummels and throws
+/// and lets go when a throw is released or the grabbed character escapes
+pub fn grab_system(
+    mut grabber_query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut CharacterMovement,
+            &mut CharacterAttackController,
+            &Moveset,
+            &GrabHand,
+            &Grabbing,
+            &Character,
+        ),
+        Without<Grabbed>,
+    >,
+    mut victim_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut GravityScale,
+            &mut Character,
+            &mut CharacterMovement,
+            &mut CharacterAttackController,
+            &mut Grabbed,
+            &Handicap,
+        ),
+        Without<Grabbing>,
+    >,
+    ruleset: Res<Ruleset>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    // Grabbers that are gone can't hold anyone
+    for (victim, _, _, mut gravity, _, movement, _, grabbed, _) in victim_query.iter_mut() {
+        if grabber_query.get(grabbed.grabber).is_err() {
+            gravity.0 = movement.normal_gravity;
+            commands.entity(victim).remove::<Grabbed>();
+        }
+    }
+
+    for (
+        grabber,
+        grabber_tf,
+        mut movement,
+        mut attack,
+        moveset,
+        hand,
+        grabbing,
+        grabber_character,
+    ) in grabber_query.iter_mut()
+    {
+        let Ok((
+            victim,
+            mut victim_tf,
+            mut victim_vel,
+            mut victim_gravity,
+            mut victim_character,
+            mut victim_movement,
+            mut victim_attack,
+            mut grabbed,
+            victim_handicap,
+        )) = victim_query.get_mut(grabbing.victim)
+        else {
+            commands.entity(grabber).remove::<Grabbing>();
+            continue;
+        };
+
+        let mirror = Vec2::new(movement.facing, 1.);
+
+        // Hold the victim in the hand
+        victim_tf.translation = (grabber_tf.translation.truncate() + hand.offset * mirror)
+            .extend(victim_tf.translation.z);
+        victim_vel.linvel = Vec2::ZERO;
+        victim_gravity.0 = 0.;
+        // Grabs go through shields, and the shield is gone until the victim is let go
+        victim_movement.is_shielding = false;
+
+        // Escape
+        let mashed = victim_movement.wants_to_jump
+            || victim_movement.wants_to_shield
+            || victim_attack.wants_to_attack.is_some();
+        victim_movement.wants_to_jump = false;
+        victim_movement.wants_to_shield = false;
+        victim_attack.wants_to_attack = None;
+
+        grabbed.escape_meter += time.delta_seconds();
+        if mashed {
+            grabbed.escape_meter += GRAB_MASH_ESCAPE;
+        }
+
+        let needed_to_escape =
+            GRAB_ESCAPE_BASE + victim_character.percentage * GRAB_ESCAPE_PER_PERCENT;
+
+        // The throw is already happening, nothing can stop it now
+        let is_throwing = attack
+            .current_attack
+            .as_ref()
+            .and_then(|(kind, _)| moveset.0.get(kind))
+            .map_or(false, |current_move| current_move.throw.is_some());
+
+        if grabbed.escape_meter >= needed_to_escape && !is_throwing {
+            victim_attack.velocity_from_knockback +=
+                Vec2::new(movement.facing * GRAB_ESCAPE_SPEED, 0.);
+            victim_gravity.0 = victim_movement.normal_gravity;
+            commands.entity(grabber).remove::<Grabbing>();
+            commands.entity(victim).remove::<Grabbed>();
+            continue;
+        }
+
+        // Throw release
+        if let Some((kind, timer)) = &attack.current_attack {
+            if let Some(throw) = moveset
+                .0
+                .get(kind)
+                .and_then(|throw_move| throw_move.throw.as_ref())
+            {
+                if timer.elapsed_secs() >= throw.release {
+                    victim_character
+                        .add_damage(ruleset.damage_taken(throw.damage, victim_handicap));
+                    let launch_velocity = launch::launch_velocity(
+                        throw.knockback
+                            * mirror
+                            * ruleset.rage_multiplier(grabber_character.percentage),
+                        victim_character.percentage,
+                    );
+                    victim_gravity.0 = victim_movement.normal_gravity;
+                    commands.entity(grabber).remove::<Grabbing>();
+                    commands.entity(victim).remove::<Grabbed>().insert((
+                        Launch::new(launch_velocity),
+                        Hitstun::from_knockback(launch_velocity),
+                    ));
+                }
+            }
+            continue;
+        }
+
+        // Pummels and throws
+        let towards_facing = movement.x * movement.facing;
+        let next_attack = if attack.wants_to_attack.is_some() {
+            Some(AttackKind::Pummel)
+        } else if movement.wants_to_jump {
+            Some(AttackKind::UpThrow)
+        } else if movement.wants_to_crouch {
+            Some(AttackKind::DownThrow)
+        } else if towards_facing > THROW_THRESHOLD {
+            Some(AttackKind::ForwardThrow)
+        } else if towards_facing < -THROW_THRESHOLD {
+            Some(AttackKind::BackThrow)
+        } else {
+            None
+        };
+
+        attack.wants_to_attack = next_attack;
+        movement.wants_to_jump = false;
+    }
+}
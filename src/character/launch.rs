@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::{status::StatusEffects, CharacterMovement};
+use crate::stage::StageFile;
+
+/// How much faster launches are for each percent of damage the character has
+const LAUNCH_SCALING_PER_PERCENT: f32 = 0.012;
+/// How much launches slow down every second, along the direction of the launch
+const LAUNCH_DECAY: f32 = 1500.;
+
+pub struct LaunchPlugin;
+
+impl Plugin for LaunchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            remove_launch_velocity
+                .after(super::character_touching_stage_check)
+                .before(super::character_movement),
+        )
+        .add_system(
+            apply_launch_velocity
+                .after(super::character_movement)
+                .after(super::abilities::movement_abilities),
+        );
+    }
+}
+
+/// The part of the character's velocity that comes from being launched.
+/// It's kept apart from the rest, so it slows down at a constant rate
+/// and the character's own movement and gravity work as usual
+#[derive(Component, Debug, Clone)]
+pub struct Launch {
+    /// In units, like knockback
+    velocity: Vec2,
+
+    /// How much of the character's velocity is the launch right now, in pixels
+    applied: Vec2,
+
+    /// Whether the character has left the stage since being launched
+    airborne: bool,
+}
+
+impl Launch {
+    pub fn new(velocity: Vec2) -> Self {
+        Self {
+            velocity,
+            applied: Vec2::ZERO,
+            airborne: false,
+        }
+    }
+
+    /// Launches a character that's already flying, the old launch is gone
+    pub fn relaunch(&mut self, velocity: Vec2) {
+        self.velocity = velocity;
+        self.airborne = false;
+    }
+
+    /// Takes the launch out of the velocity, the rest of the velocity stays
+    pub fn remove_from(&mut self, vel: &mut Velocity) {
+        vel.linvel -= self.applied;
+        self.applied = Vec2::ZERO;
+    }
+}
+
+/// How fast a hit with `knockback` launches a character with `percentage` damage
+pub fn launch_velocity(knockback: Vec2, percentage: f32) -> Vec2 {
+    knockback * (1. + percentage * LAUNCH_SCALING_PER_PERCENT)
+}
+
+/// Takes last frame's launch out of the velocity,
+/// so `character_movement` only sees the character's own
+fn remove_launch_velocity(
+    mut character_query: Query<(
+        &mut Velocity,
+        &CharacterMovement,
+        &StatusEffects,
+        &mut Launch,
+    )>,
+) {
+    for (mut vel, movement, statuses, mut launch) in character_query.iter_mut() {
+        let touching_stage = !movement.is_not_touching_stage() || movement.is_on_stage_ceiling();
+
+        if !touching_stage {
+            launch.airborne = true;
+        } else if launch.airborne && !statuses.is_frozen() {
+            // Hitting the stage ends the launch, the physics already stopped the character.
+            // Ice blocks keep sliding until the launch is over
+            launch.velocity = Vec2::ZERO;
+            launch.applied = Vec2::ZERO;
+            continue;
+        }
+
+        launch.remove_from(&mut vel);
+    }
+}
+
+/// Slows the launch down and puts it back into the velocity
+fn apply_launch_velocity(
+    mut character_query: Query<(Entity, &mut Velocity, &mut Launch)>,
+    stage: Res<StageFile>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut vel, mut launch) in character_query.iter_mut() {
+        let speed = (launch.velocity.length() - LAUNCH_DECAY * time.delta_seconds()).max(0.);
+        launch.velocity = launch.velocity.normalize_or_zero() * speed;
+
+        if launch.velocity == Vec2::ZERO {
+            commands.entity(entity).remove::<Launch>();
+            continue;
+        }
+
+        launch.applied = launch.velocity * stage.physics.pixels_per_unit;
+        vel.linvel += launch.applied;
+    }
+}
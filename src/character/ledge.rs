@@ -0,0 +1,327 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::stage::{Ledge, StageFile};
+
+use super::{
+    grab::Grabbed, moves::AttackKind, CharacterAttackController, CharacterMovement, Helpless,
+    Intangibility, MovementAbilities,
+};
+
+/// How long the character is intangible after grabbing a ledge
+const LEDGE_INTANGIBILITY: f32 = 1.;
+/// How long the character is intangible while getting up from a ledge
+const LEDGE_GETUP_INTANGIBILITY: f32 = 0.3;
+/// After this long hanging, the character lets go on its own
+const LEDGE_MAX_HANG_TIME: f32 = 5.;
+/// How long after letting go of a ledge the character can't grab one again
+const LEDGE_REGRAB_COOLDOWN: f32 = 0.5;
+/// How far from the ledge a roll puts the character
+const LEDGE_ROLL_DISTANCE: f32 = 250.;
+/// The speed at which a character gets pushed away when its ledge gets taken
+const LEDGE_TRUMP_SPEED: Vec2 = Vec2::new(300., 500.);
+
+pub struct LedgePlugin;
+
+impl Plugin for LedgePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(ledge_grab).add_system(ledge_hang);
+    }
+}
+
+/// The volume characters grab ledges with.
+/// It's centered on the top of the character
+#[derive(Component, Debug, Clone)]
+pub struct LedgeGrabber {
+    /// How far past the character's collider ledges can be grabbed from
+    pub reach: Vec2,
+
+    /// Ledge intangibility only comes back after touching the ground
+    can_get_intangibility: bool,
+
+    /// Set after letting go of a ledge, so it isn't grabbed again right away
+    regrab_cooldown: Option<Timer>,
+
+    /// How far ledges can be grabbed from while a tether is out
+    tether_reach: Option<f32>,
+}
+
+/// Present while the character is hanging from a ledge
+#[derive(Component, Debug, Clone)]
+pub struct LedgeHang {
+    ledge: Entity,
+    hang_timer: Timer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LedgeGetup {
+    Neutral,
+    Roll,
+    Jump,
+    Attack,
+    Drop,
+}
+
+impl Default for LedgeGrabber {
+    fn default() -> Self {
+        Self {
+            reach: Vec2::new(30., 40.),
+            can_get_intangibility: true,
+            regrab_cooldown: None,
+            tether_reach: None,
+        }
+    }
+}
+
+impl LedgeGrabber {
+    pub fn set_tether_reach(&mut self, reach: Option<f32>) {
+        self.tether_reach = reach;
+    }
+
+    fn let_go(&mut self) {
+        self.regrab_cooldown = Some(Timer::from_seconds(LEDGE_REGRAB_COOLDOWN, TimerMode::Once));
+    }
+}
+
+pub(super) fn collider_half_extents(collider: &Collider) -> Vec2 {
+    collider
+        .as_cuboid()
+        .map(|cuboid| cuboid.half_extents())
+        .unwrap_or_default()
+}
+
+fn ledge_grab(
+    mut character_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Collider,
+            &Velocity,
+            &mut CharacterMovement,
+            &MovementAbilities,
+            &mut LedgeGrabber,
+            &mut GravityScale,
+        ),
+        (Without<LedgeHang>, Without<Grabbed>),
+    >,
+    mut hanging_query: Query<
+        (
+            Entity,
+            &LedgeHang,
+            &mut LedgeGrabber,
+            &mut Velocity,
+            &mut GravityScale,
+            &CharacterMovement,
+        ),
+        With<LedgeHang>,
+    >,
+    ledge_query: Query<(Entity, &GlobalTransform, &Ledge)>,
+    stage: Res<StageFile>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let mut grabbed_this_frame = Vec::new();
+
+    for (entity, tf, collider, vel, mut movement, abilities, mut grabber, mut gravity) in
+        character_query.iter_mut()
+    {
+        if let Some(timer) = &mut grabber.regrab_cooldown {
+            if timer.tick(time.delta()).finished() {
+                grabber.regrab_cooldown = None;
+            }
+        }
+
+        if movement.is_on_stage() {
+            grabber.can_get_intangibility = true;
+        }
+
+        // Tethers can grab ledges on the way up
+        if grabber.regrab_cooldown.is_some()
+            || !movement.is_not_touching_stage()
+            || (vel.linvel.y > 0. && grabber.tether_reach.is_none())
+        {
+            continue;
+        }
+
+        let half_extents = collider_half_extents(collider);
+        let grab_box_center = tf.translation.truncate() + Vec2::new(0., half_extents.y);
+        let grab_box_half_extents = (Vec2::new(half_extents.x, 0.) + grabber.reach)
+            .max(Vec2::splat(grabber.tether_reach.unwrap_or_default()));
+
+        let Some((ledge_entity, _, ledge)) =
+            ledge_query.iter().find(|(entity, ledge_tf, ledge)| {
+                let ledge_position = ledge_tf.translation().truncate();
+                // Only grab from off stage
+                (tf.translation.x - ledge_position.x) * ledge.direction > 0.
+                    && (ledge_position - grab_box_center)
+                        .abs()
+                        .cmple(grab_box_half_extents)
+                        .all()
+                    && !grabbed_this_frame.contains(entity)
+            })
+        else {
+            continue;
+        };
+
+        // Ledge trumping
+        for (
+            hanging_entity,
+            hang,
+            mut hanging_grabber,
+            mut hanging_vel,
+            mut hanging_gravity,
+            hanging_movement,
+        ) in hanging_query.iter_mut()
+        {
+            if hang.ledge != ledge_entity {
+                continue;
+            }
+
+            hanging_vel.linvel =
+                LEDGE_TRUMP_SPEED * Vec2::new(ledge.direction, 1.) * stage.physics.pixels_per_unit;
+            hanging_gravity.0 = hanging_movement.normal_gravity;
+            hanging_grabber.let_go();
+            commands
+                .entity(hanging_entity)
+                .remove::<(LedgeHang, Intangibility)>();
+        }
+
+        if grabber.can_get_intangibility {
+            grabber.can_get_intangibility = false;
+            commands
+                .entity(entity)
+                .insert(Intangibility(Timer::from_seconds(
+                    LEDGE_INTANGIBILITY,
+                    TimerMode::Once,
+                )));
+        }
+
+        gravity.0 = 0.;
+        movement.current_air_jumps = abilities.max_air_jumps(&movement);
+        grabbed_this_frame.push(ledge_entity);
+        commands
+            .entity(entity)
+            .remove::<Helpless>()
+            .insert(LedgeHang {
+                ledge: ledge_entity,
+                hang_timer: Timer::from_seconds(LEDGE_MAX_HANG_TIME, TimerMode::Once),
+            });
+    }
+}
+
+fn ledge_hang(
+    mut character_query: Query<(
+        Entity,
+        &mut Transform,
+        &Collider,
+        &mut Velocity,
+        &mut GravityScale,
+        &mut CharacterMovement,
+        &mut CharacterAttackController,
+        &mut LedgeGrabber,
+        &mut LedgeHang,
+    )>,
+    ledge_query: Query<(&GlobalTransform, &Ledge)>,
+    stage: Res<StageFile>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (
+        entity,
+        mut tf,
+        collider,
+        mut vel,
+        mut gravity,
+        mut movement,
+        mut attack,
+        mut grabber,
+        mut hang,
+    ) in character_query.iter_mut()
+    {
+        let Ok((ledge_tf, ledge)) = ledge_query.get(hang.ledge) else {
+            // The ledge is gone, so there's nothing to hang from
+            gravity.0 = movement.normal_gravity;
+            commands.entity(entity).remove::<LedgeHang>();
+            continue;
+        };
+
+        let half_extents = collider_half_extents(collider);
+        let ledge_position = ledge_tf.translation().truncate();
+        // Towards the stage
+        let inwards = -ledge.direction;
+
+        let getup = if hang.hang_timer.tick(time.delta()).finished() {
+            Some(LedgeGetup::Drop)
+        } else if movement.wants_to_jump {
+            Some(LedgeGetup::Jump)
+        } else if attack.wants_to_attack.is_some() {
+            Some(LedgeGetup::Attack)
+        } else if movement.wants_to_shield {
+            Some(LedgeGetup::Roll)
+        } else if movement.wants_to_drop_through || movement.x * inwards < 0. {
+            Some(LedgeGetup::Drop)
+        } else if movement.x * inwards > 0. {
+            Some(LedgeGetup::Neutral)
+        } else {
+            None
+        };
+
+        // Reset the variables, since `character_movement` doesn't run while hanging
+        movement.wants_to_jump = false;
+        movement.wants_to_fastfall = false;
+        movement.wants_to_drop_through = false;
+        movement.wants_to_shield = false;
+        attack.wants_to_attack = None;
+
+        let Some(getup) = getup else {
+            // Keep hanging
+            tf.translation = (ledge_position
+                + Vec2::new(ledge.direction * half_extents.x, -half_extents.y))
+            .extend(tf.translation.z);
+            vel.linvel = Vec2::ZERO;
+            continue;
+        };
+
+        let on_stage = ledge_position + Vec2::new(inwards * half_extents.x, half_extents.y);
+
+        match getup {
+            LedgeGetup::Neutral | LedgeGetup::Attack => {
+                tf.translation = on_stage.extend(tf.translation.z);
+            }
+            LedgeGetup::Roll => {
+                tf.translation = (on_stage + Vec2::new(inwards * LEDGE_ROLL_DISTANCE, 0.))
+                    .extend(tf.translation.z);
+            }
+            LedgeGetup::Jump => {
+                vel.linvel.y = movement.jump_boost * stage.physics.pixels_per_unit;
+            }
+            LedgeGetup::Drop => {}
+        }
+
+        if matches!(
+            getup,
+            LedgeGetup::Neutral | LedgeGetup::Roll | LedgeGetup::Attack
+        ) {
+            vel.linvel = Vec2::ZERO;
+            commands
+                .entity(entity)
+                .insert(Intangibility(Timer::from_seconds(
+                    LEDGE_GETUP_INTANGIBILITY,
+                    TimerMode::Once,
+                )));
+        } else {
+            // Jumping or dropping loses whatever is left of the ledge intangibility
+            commands.entity(entity).remove::<Intangibility>();
+        }
+
+        if getup == LedgeGetup::Attack {
+            attack.wants_to_attack = Some(AttackKind::LedgeAttack);
+        }
+
+        gravity.0 = movement.normal_gravity;
+        movement.is_fastfalling = false;
+        movement.facing = inwards;
+        grabber.let_go();
+        commands.entity(entity).remove::<LedgeHang>();
+    }
+}
@@ -1,20 +1,81 @@
+mod abilities;
+mod blown;
+mod clank;
+mod grab;
+mod launch;
+mod ledge;
+mod moves;
 mod player;
+mod projectile;
+mod push;
+mod rider;
+mod stale;
+mod status;
+mod tech;
 
-use bevy::prelude::*;
+use bevy::{ecs::query::WorldQuery, prelude::*};
 use bevy_rapier2d::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::stage::Stage;
+use crate::{
+    layers::ColliderKind,
+    ruleset::{Handicap, Ruleset},
+    stage::{SoftPlatform, Stage, StageFile},
+};
+
+/// How long a hit keeps the character from acting for each unit of knockback, in seconds
+const HITSTUN_PER_KNOCKBACK: f32 = 0.0006;
+/// Hits with at least this much knockback make the character tumble, so it can tech
+const TUMBLE_KNOCKBACK: f32 = 600.;
+/// Characters can't have more damage than this
+const MAX_PERCENTAGE: f32 = 999.9;
+
+pub use self::{
+    abilities::MovementAbilities,
+    blown::Blown,
+    moves::Moveset,
+    stale::StaleMoves,
+    status::{StatusEffect, StatusExpired},
+};
+use self::{
+    clank::Rebound,
+    grab::{GrabHand, Grabbed, Grabbing},
+    launch::Launch,
+    ledge::{LedgeGrabber, LedgeHang},
+    moves::{AttackKind, Defense, Hitbox, HitboxKind, KnockbackAngle, ProjectileSpawn},
+    projectile::Projectile,
+    push::Pushed,
+    rider::PlatformRider,
+    status::{ApplyStatus, StatusEffects},
+    tech::Knockdown,
+};
 
 pub struct CharacterPlugin;
 
 impl Plugin for CharacterPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(player::PlayerPlugin)
+            .add_plugin(ledge::LedgePlugin)
+            .add_plugin(projectile::ProjectilePlugin)
+            .add_plugin(tech::TechPlugin)
+            .add_plugin(launch::LaunchPlugin)
+            .add_plugin(status::StatusPlugin)
+            .add_plugin(rider::RiderPlugin)
+            .add_plugin(blown::BlownPlugin)
+            .add_plugin(push::PushPlugin)
             .add_system(character_touching_stage_check)
+            .add_system(air_friction_system.after(character_touching_stage_check))
+            .add_system(grab::grab_system.before(character_movement))
             .add_system(character_movement)
-            .add_system(character_attack)
-            .add_system(attack_system);
+            .add_system(abilities::movement_abilities.after(character_movement))
+            .add_system(character_attack.after(character_movement))
+            .add_system(clank::clank_system.before(attack_system))
+            .add_system(attack_system)
+            .add_system(clank::rebound_system)
+            .add_system(intangibility_system)
+            .add_system(hitstun_system)
+            .add_system(helpless_system)
+            .add_system(blast_zone_system);
     }
 }
 
@@ -22,19 +83,81 @@ impl Plugin for CharacterPlugin {
 pub struct CharacterBundle {
     pub typ: Character,
     pub movement: CharacterMovement,
+    pub abilities: MovementAbilities,
     pub vel: Velocity,
     pub grav: GravityScale,
     pub damping: Damping,
     pub kincharcont: KinematicCharacterController,
     pub attacks: CharacterAttackController,
+    pub moveset: Moveset,
+    pub stale_moves: StaleMoves,
+    pub handicap: Handicap,
+    pub statuses: StatusEffects,
+    pub rider: PlatformRider,
+    pub blown: Blown,
+    pub pushed: Pushed,
+    pub grab_hand: GrabHand,
+    pub ledge_grabber: LedgeGrabber,
     pub name: Name,
 }
 
 #[derive(Component, Debug, Clone, Default)]
 pub struct Character {
+    /// Which player it is, starting at 0. Handicaps go by it, so they don't depend on the name
+    pub slot: usize,
     pub percentage: f32,
 }
 
+impl Character {
+    /// Negative damage heals
+    pub fn add_damage(&mut self, damage: f32) {
+        self.percentage = (self.percentage + damage).clamp(0., MAX_PERCENTAGE);
+    }
+
+    /// Back to the damage the character starts with, like when it spawns or respawns
+    pub fn reset_damage(&mut self, handicap: &Handicap) {
+        self.percentage = 0.;
+        self.add_damage(handicap.starting_percentage);
+    }
+}
+
+/// While the timer isn't finished, the character can't be hit
+#[derive(Component, Debug, Clone)]
+pub struct Intangibility(pub Timer);
+
+/// Special fall. The character can only drift and grab ledges until it lands
+#[derive(Component, Debug, Clone)]
+pub struct Helpless {
+    /// How long the character can't act for after landing, in seconds
+    pub landing_lag: f32,
+}
+
+/// While present, the character can't move, jump or attack
+#[derive(Component, Debug, Clone)]
+pub struct LandingLag(pub Timer);
+
+/// After getting hit, the character can't act until the timer is finished
+#[derive(Component, Debug, Clone)]
+pub struct Hitstun {
+    timer: Timer,
+
+    /// Tumbling characters can tech when they hit the stage, or get knocked down
+    tumble: bool,
+
+    /// Whether the character has left the stage since getting hit
+    airborne: bool,
+
+    /// Set when shield is pressed, hitting the stage while it's going is a tech
+    tech_window: Option<Timer>,
+
+    /// Shield can't open a new tech window until this is over
+    tech_lockout: Option<Timer>,
+
+    /// Meteor smashed characters bounce off the stage with this
+    /// velocity if they don't tech, instead of getting knocked down
+    bounce: Option<Vec2>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 enum PossibleValues {
     F32(f32),
@@ -42,7 +165,28 @@ enum PossibleValues {
     Bool(bool),
 }
 
+/// What gets read from a character's file in `assets/characters`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct CharacterFile {
+    pub name: String,
+    pub movement: CharacterMovement,
+    pub abilities: MovementAbilities,
+    pub moveset: Moveset,
+    pub grab_hand: GrabHand,
+}
+
+impl CharacterFile {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = std::fs::read_to_string(path)
+            .map_err(|error| format!("Couldn't read {path}: {error}"))?;
+        serde_json::from_str(&file)
+            .map_err(|error| format!("{path} isn't a valid character: {error}"))
+    }
+}
+
 #[derive(Serialize, Deserialize, Component, Debug, Clone)]
+#[serde(default)]
 pub struct CharacterMovement {
     // Constants
     pub speed_air: f32,
@@ -82,20 +226,163 @@ pub struct CharacterMovement {
 
     /// Helper to track the last walljump direction
     walljump_direction: f32,
+
+    /// Whether the character wants to drop through the soft platform it is on
+    wants_to_drop_through: bool,
+
+    /// Whether the character is falling through a soft platform.
+    /// Read by the stage physics hooks
+    is_dropping_through: bool,
+
+    /// Whether the stage the character is standing on is a soft platform
+    is_standing_on_soft_platform: bool,
+
+    /// The part of the stage the character is standing on, if any
+    #[serde(skip)]
+    standing_on: Option<Entity>,
+
+    /// Whether the character wants to shield.
+    /// Also used to roll off ledges
+    wants_to_shield: bool,
+
+    /// Which way the character is looking, 1. for right and -1. for left
+    facing: f32,
+
+    /// Whether jump is being held. Used by float and glide
+    is_holding_jump: bool,
+
+    /// Whether the character wants to crouch, or crawl if it can
+    wants_to_crouch: bool,
+
+    /// Whether the character is shielding. Shields block hits, but not grabs
+    is_shielding: bool,
+
+    /// Whether shield was just pressed. Techs if the character
+    /// hits the stage soon after while tumbling
+    wants_to_tech: bool,
 }
 
 #[derive(Component, Debug, Clone, Default)]
 pub struct CharacterAttack {
     damage: f32,
-    has_attacked: Vec<Entity>,
     knockback: Vec2,
+    kind: HitboxKind,
+    transcendent: bool,
+    angle: KnockbackAngle,
+    rehit_rate: Option<f32>,
+    status: Option<StatusEffect>,
+
+    /// The move the hitbox or projectile is from, if it still belongs to it
+    attack_kind: Option<AttackKind>,
+
+    /// Who the projectile has hit. Hitboxes share the hit tracking
+    /// of their move instead, in `CharacterAttackController`
+    has_attacked: Vec<Entity>,
+
+    /// Clanked hitboxes can't hit anyone anymore
+    clanked: bool,
+}
+
+/// A hitbox that belongs to the stage instead of a character.
+/// Nobody gets credit for its hits, but they work like any other
+#[derive(Component, Debug, Clone, Default)]
+pub struct HazardHitbox {
+    /// Who it hit and when, in seconds since it came out
+    hit_tracking: Vec<(Entity, f32)>,
+    elapsed: f32,
+}
+
+impl HazardHitbox {
+    /// Everything a hazard needs to hit characters but the collider and the transform
+    pub fn bundle(
+        damage: f32,
+        knockback: Vec2,
+        rehit_rate: Option<f32>,
+        status: Option<StatusEffect>,
+    ) -> impl Bundle {
+        (
+            CharacterAttack {
+                damage,
+                knockback,
+                rehit_rate,
+                status,
+                ..default()
+            },
+            HazardHitbox::default(),
+            Sensor,
+            ActiveEvents::COLLISION_EVENTS,
+            ColliderKind::Hazard.collision_groups(),
+        )
+    }
+
+    fn can_hit(&self, victim: Entity, rehit_rate: Option<f32>) -> bool {
+        let Some((_, last_hit)) = self.hit_tracking.iter().find(|(hit, _)| *hit == victim) else {
+            return true;
+        };
+        rehit_rate.map_or(false, |rehit_rate| self.elapsed - last_hit >= rehit_rate)
+    }
+
+    fn track_hit(&mut self, victim: Entity) {
+        self.hit_tracking.retain(|(hit, _)| *hit != victim);
+        self.hit_tracking.push((victim, self.elapsed));
+    }
 }
 
 #[derive(Component, Debug, Clone, Default)]
 pub struct CharacterAttackController {
-    attack_timer: Option<Timer>,
-    wants_to_forward_air: bool,
+    /// The move being done and how long it's been going for
+    current_attack: Option<(AttackKind, Timer)>,
+    wants_to_attack: Option<AttackKind>,
     velocity_from_knockback: Vec2,
+
+    /// Whether the current attack was started in the air.
+    /// Aerials end as soon as the character lands on a soft platform
+    is_aerial_attack: bool,
+
+    /// The hitboxes of the current attack that are out, by their index in the move
+    active_hitboxes: Vec<(usize, Entity)>,
+
+    /// How many of the current attack's projectiles have been thrown
+    projectiles_thrown: usize,
+
+    /// Who the current attack has hit, and how far into the move, in seconds
+    hit_tracking: Vec<(Entity, f32)>,
+
+    /// Set when a counter catches a hit: the counterattack
+    /// to do and the damage its hitboxes should deal
+    countered: Option<(AttackKind, f32)>,
+
+    /// The damage the hitboxes of the current attack deal, if it's a counterattack
+    counter_damage: Option<f32>,
+
+    /// How much the current attack is weakened by stale move negation
+    stale_multiplier: f32,
+
+    /// Whether the current attack has connected, so it's only added to `StaleMoves` once
+    has_connected: bool,
+}
+
+impl Hitstun {
+    fn new(duration: f32, tumble: bool) -> Self {
+        Self {
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+            tumble,
+            airborne: false,
+            tech_window: None,
+            tech_lockout: None,
+            bounce: None,
+        }
+    }
+
+    fn from_knockback(knockback: Vec2) -> Self {
+        Self {
+            bounce: (knockback.y < 0.).then(|| moves::ground_bounce(knockback)),
+            ..Self::new(
+                knockback.length() * HITSTUN_PER_KNOCKBACK,
+                knockback.length() >= TUMBLE_KNOCKBACK,
+            )
+        }
+    }
 }
 
 impl CharacterMovement {
@@ -103,6 +390,10 @@ impl CharacterMovement {
         self.stage_touch_force.y > 0.
     }
 
+    fn is_on_stage_ceiling(&self) -> bool {
+        self.stage_touch_force.y < 0.
+    }
+
     /// The difference between the function and
     /// `!is_on_floor()` is that this one will return
     /// `false` if the character is on a wall
@@ -123,14 +414,74 @@ impl CharacterMovement {
     fn jump(&mut self) {
         self.wants_to_jump = true;
     }
+
+    fn hold_jump(&mut self) {
+        self.is_holding_jump = true;
+    }
+
+    fn crouch(&mut self) {
+        self.wants_to_crouch = true;
+    }
+
+    fn shield(&mut self) {
+        self.wants_to_shield = true;
+    }
+
+    fn tech(&mut self) {
+        self.wants_to_tech = true;
+    }
+
+    fn drop_through(&mut self) {
+        self.wants_to_drop_through = true;
+    }
+
+    pub fn is_dropping_through(&self) -> bool {
+        self.is_dropping_through
+    }
+
+    fn is_on_soft_platform(&self) -> bool {
+        self.is_on_stage() && self.is_standing_on_soft_platform
+    }
 }
 
 impl CharacterAttackController {
     pub fn forward_air(&mut self) {
-        self.wants_to_forward_air = true;
+        self.wants_to_attack = Some(AttackKind::ForwardAir);
+    }
+    pub fn up_special(&mut self) {
+        self.wants_to_attack = Some(AttackKind::UpSpecial);
+    }
+    pub fn side_special(&mut self) {
+        self.wants_to_attack = Some(AttackKind::SideSpecial);
+    }
+    /// Becomes a zair in the air
+    pub fn grab(&mut self) {
+        self.wants_to_attack = Some(AttackKind::Grab);
     }
     fn is_attacking(&self) -> bool {
-        self.attack_timer.is_some()
+        self.current_attack.is_some()
+    }
+
+    fn attack_elapsed_secs(&self) -> f32 {
+        self.current_attack
+            .as_ref()
+            .map_or(0., |(_, timer)| timer.elapsed_secs())
+    }
+
+    /// Whether the current attack can hit `victim`, given how often it can hit the same character
+    fn can_hit(&self, victim: Entity, rehit_rate: Option<f32>) -> bool {
+        let Some((_, last_hit)) = self.hit_tracking.iter().find(|(hit, _)| *hit == victim) else {
+            return true;
+        };
+        rehit_rate.map_or(false, |rehit_rate| {
+            self.attack_elapsed_secs() - last_hit >= rehit_rate
+        })
+    }
+
+    fn track_hit(&mut self, victim: Entity) {
+        let elapsed = self.attack_elapsed_secs();
+        self.hit_tracking.retain(|(hit, _)| *hit != victim);
+        self.hit_tracking.push((victim, elapsed));
     }
 }
 
@@ -154,6 +505,16 @@ impl Default for CharacterMovement {
             wants_to_fastfall: default(),
             stage_touch_force: default(),
             walljump_direction: default(),
+            wants_to_drop_through: default(),
+            is_dropping_through: default(),
+            is_standing_on_soft_platform: default(),
+            standing_on: default(),
+            wants_to_shield: default(),
+            facing: 1.,
+            is_holding_jump: default(),
+            wants_to_crouch: default(),
+            is_shielding: default(),
+            wants_to_tech: default(),
         };
     }
 }
@@ -161,10 +522,13 @@ impl Default for CharacterMovement {
 fn character_touching_stage_check(
     mut contact_force_events: EventReader<ContactForceEvent>,
     stage_query: Query<&Stage>,
+    soft_platform_query: Query<&SoftPlatform>,
     mut character_query: Query<&mut CharacterMovement>,
 ) {
     character_query.for_each_mut(|mut character| {
         character.stage_touch_force *= 0.; // Reset the variable
+        character.is_standing_on_soft_platform = false;
+        character.standing_on = None;
     });
 
     'contact_loop: for contact_force_event in contact_force_events.iter() {
@@ -192,38 +556,121 @@ fn character_touching_stage_check(
             }
         }
 
-        let mut character = if character_query.get(contact_force_event.collider1).is_ok() {
-            character_query.get_mut(contact_force_event.collider1)
-        } else {
-            character_query.get_mut(contact_force_event.collider2)
-        }
-        .unwrap();
+        let (character, stage_entity) =
+            if character_query.get(contact_force_event.collider1).is_ok() {
+                (
+                    character_query.get_mut(contact_force_event.collider1),
+                    contact_force_event.collider2,
+                )
+            } else {
+                (
+                    character_query.get_mut(contact_force_event.collider2),
+                    contact_force_event.collider1,
+                )
+            };
+        let mut character = character.unwrap();
 
         character.stage_touch_force = contact_force_event.total_force;
+        character.is_standing_on_soft_platform = soft_platform_query.get(stage_entity).is_ok();
+        if character.is_on_stage() {
+            character.standing_on = Some(stage_entity);
+        }
+    }
+}
+
+/// Characters get the stage's air friction while they're in the air
+fn air_friction_system(
+    mut character_query: Query<(&CharacterMovement, &mut Damping)>,
+    stage: Res<StageFile>,
+) {
+    for (movement, mut damping) in character_query.iter_mut() {
+        damping.linear_damping = if movement.is_on_stage() {
+            0.
+        } else {
+            stage.physics.air_friction
+        };
     }
 }
 
 /// Applies the movement to the character.
+/// Characters hanging from a ledge are moved by the ledge systems instead,
+/// and grabbed characters by the grab system
 fn character_movement(
-    mut character_query: Query<(
-        &mut CharacterAttackController,
-        &mut CharacterMovement,
-        &mut Velocity,
-        &mut GravityScale,
-    )>,
+    mut character_query: Query<
+        (
+            &mut CharacterAttackController,
+            &mut CharacterMovement,
+            &MovementAbilities,
+            &mut Velocity,
+            &mut GravityScale,
+            Option<&Helpless>,
+            Option<&LandingLag>,
+            Option<&Grabbing>,
+            Option<&Rebound>,
+            Option<&Hitstun>,
+            &StatusEffects,
+        ),
+        (Without<LedgeHang>, Without<Grabbed>, Without<Knockdown>),
+    >,
+    stage: Res<StageFile>,
 ) {
-    for (mut attack_controller, mut movement, mut vel, mut gravity) in character_query.iter_mut() {
+    let scale = stage.physics.pixels_per_unit;
+
+    for (
+        mut attack_controller,
+        mut movement,
+        abilities,
+        mut vel,
+        mut gravity,
+        helpless,
+        landing_lag,
+        grabbing,
+        rebound,
+        hitstun,
+        statuses,
+    ) in character_query.iter_mut()
+    {
+        let can_act = helpless.is_none()
+            && landing_lag.is_none()
+            && grabbing.is_none()
+            && rebound.is_none()
+            && hitstun.is_none()
+            && !statuses.is_disabled();
+        if landing_lag.is_some() || rebound.is_some() || statuses.holds_in_place() {
+            movement.x = 0.;
+        }
+        if !can_act {
+            movement.wants_to_jump = false;
+        }
+
         // Horizontal Movement
-        if movement.is_on_stage() {
-            vel.linvel.x = movement.x * movement.speed_floor;
-        } else {
-            // Using the same thing as in 2 lines above makes the movement feel very awkward
-            vel.linvel.x = (vel.linvel.x + movement.x * movement.speed_air)
-                .clamp(-movement.max_speed_air, movement.max_speed_air);
+        // Characters in hitstun go wherever the knockback sends them, and so do frozen ones
+        if hitstun.is_none() && !statuses.is_frozen() {
+            if movement.is_on_stage() {
+                let speed = match abilities.crawl_speed() {
+                    Some(crawl_speed) if movement.wants_to_crouch => crawl_speed,
+                    _ => movement.speed_floor,
+                };
+                vel.linvel.x = movement.x * speed * scale;
+            } else {
+                // Using the same thing as in 2 lines above makes the movement feel very awkward
+                let max_speed = movement.max_speed_air * scale;
+                vel.linvel.x = (vel.linvel.x + movement.x * movement.speed_air * scale)
+                    .clamp(-max_speed, max_speed);
+            }
+        }
+
+        // Shield
+        movement.is_shielding = movement.wants_to_shield
+            && movement.is_on_stage()
+            && can_act
+            && !attack_controller.is_attacking();
+        if movement.is_shielding || grabbing.is_some() {
+            vel.linvel.x = 0.;
         }
 
         // Knockback
-        vel.linvel += attack_controller.velocity_from_knockback;
+        vel.linvel += attack_controller.velocity_from_knockback * scale;
         attack_controller.velocity_from_knockback = Vec2::ZERO;
 
         // FastFall
@@ -234,7 +681,7 @@ fn character_movement(
 
         // Apply fastfall
         if just_started_fastfalling {
-            vel.linvel.y -= movement.fastfall_initial_speed;
+            vel.linvel.y -= movement.fastfall_initial_speed * scale;
             gravity.0 = movement.fastfalling_gravity;
             movement.is_fastfalling = true;
         }
@@ -257,9 +704,16 @@ fn character_movement(
         movement.was_fastfalling_last_frame = movement.is_fastfalling;
 
         // Jump
-        let gonna_inevitably_walljump = movement.stage_touch_force.x * movement.x > 0.
+        // Characters that can cling to walls only walljump when they jump off one
+        let touching_wall_to_jump = if abilities.wall_cling.is_some() {
+            abilities.is_clinging() && movement.wants_to_jump
+        } else {
+            movement.stage_touch_force.x * movement.x > 0.
+        };
+        let gonna_inevitably_walljump = touching_wall_to_jump
             && movement.walljump_direction * movement.x <= 0.
-            && movement.can_walljump;
+            && movement.can_walljump
+            && can_act;
 
         // This is done in order to track direction of the wall that was jumped in order
         // to replicate the behavior in smash
@@ -270,9 +724,11 @@ fn character_movement(
 
         if (movement.wants_to_jump && movement.current_air_jumps > 0) || gonna_inevitably_walljump {
             if movement.is_not_touching_stage() {
+                vel.linvel.y = abilities.air_jump_boost(&movement) * scale;
                 movement.current_air_jumps -= 1;
+            } else {
+                vel.linvel.y = movement.jump_boost * scale;
             }
-            vel.linvel.y = movement.jump_boost;
             movement.is_fastfalling = false;
 
             // In smash, when you jump, for some reason
@@ -281,101 +737,715 @@ fn character_movement(
             // thinks you are on floor, therefore
             // I am gonna use the same as
             // the one I use when the character is on floor
-            vel.linvel.x = movement.x * movement.speed_floor;
+            vel.linvel.x = movement.x * movement.speed_floor * scale;
+
+            // Jump away from the wall that was being held
+            if gonna_inevitably_walljump && abilities.is_clinging() {
+                vel.linvel.x = -vel.linvel.x;
+            }
+        }
+
+        // Drop through soft platforms
+        if movement.wants_to_drop_through && movement.is_on_soft_platform() {
+            movement.is_dropping_through = true;
+        }
+        // The physics hooks will keep ignoring the platform
+        // until the character is out of it
+        if !movement.is_on_soft_platform() {
+            movement.is_dropping_through = false;
         }
 
         // reset the variables
         movement.wants_to_jump = false;
+        movement.wants_to_drop_through = false;
+        movement.wants_to_shield = false;
+        movement.wants_to_crouch = false;
         if movement.is_on_stage() {
-            movement.current_air_jumps = movement.max_air_jumps;
+            movement.current_air_jumps = abilities.max_air_jumps(&movement);
             movement.walljump_direction = 0.;
+
+            if movement.x != 0. {
+                movement.facing = movement.x.signum();
+            }
         }
     }
 }
 
+/// Hitboxes, projectiles and hazards hitting characters.
+/// Overlaps are checked every frame, so multi-hit moves can hit again
+#[allow(clippy::too_many_arguments)]
 fn attack_system(
-    mut attack: Query<&mut CharacterAttack>,
-    mut attacked: Query<(Entity, &mut Character, &mut CharacterAttackController)>,
-    mut collision_event: EventReader<CollisionEvent>,
+    mut attack_query: Query<(
+        Entity,
+        &mut CharacterAttack,
+        Option<&Parent>,
+        Option<&mut Projectile>,
+        Option<&mut HazardHitbox>,
+    )>,
+    mut character_query: Query<(
+        &mut Character,
+        &mut CharacterAttackController,
+        &CharacterMovement,
+        &Moveset,
+        &mut StaleMoves,
+        &mut Velocity,
+        Option<&Intangibility>,
+        Option<&Grabbing>,
+        Option<&Grabbed>,
+        Option<&Knockdown>,
+        Option<&mut Launch>,
+        &Handicap,
+    )>,
+    rapier_context: Res<RapierContext>,
+    ruleset: Res<Ruleset>,
+    stage: Res<StageFile>,
+    time: Res<Time>,
+    mut status_events: EventWriter<ApplyStatus>,
+    mut commands: Commands,
 ) {
-    for collision in collision_event.iter() {
-        if let CollisionEvent::Started(col1, col2, _) = collision {
-            let (attacked_entity, mut attacked_character, mut attacked_controller) =
-                match attacked.get_mut(*col1) {
-                    Ok(uwu) => uwu,
-                    Err(_) => attacked.get_mut(*col2).unwrap(),
-                };
-            let mut attack = match attack.get_mut(*col1) {
-                Ok(uwu) => uwu,
-                Err(_) => attack.get_mut(*col2).unwrap(),
+    // Characters that got into a grab this frame, the grab components aren't there yet
+    let mut grabs_this_frame = Vec::new();
+
+    for (attack_entity, mut attack, parent, mut projectile, mut hazard) in attack_query.iter_mut() {
+        // Projectiles belong to whoever threw or last reflected them, hazards to nobody
+        let attacker = match (&projectile, &hazard, parent) {
+            (Some(projectile), _, _) => Some(projectile.owner),
+            (None, Some(_), _) => None,
+            (None, None, Some(parent)) => Some(parent.get()),
+            (None, None, None) => continue,
+        };
+
+        if attack.clanked || matches!(attack.kind, HitboxKind::Reflect | HitboxKind::Absorb) {
+            continue;
+        }
+
+        if let Some(hazard) = &mut hazard {
+            hazard.elapsed += time.delta_seconds();
+        }
+
+        for (col1, col2, intersecting) in rapier_context.intersections_with(attack_entity) {
+            let attacked_entity = if col1 == attack_entity { col2 } else { col1 };
+
+            // Anything that isn't a character is handled somewhere else
+            if !intersecting || Some(attacked_entity) == attacker {
+                continue;
+            }
+            let Ok((
+                _,
+                attacked_controller,
+                attacked_movement,
+                attacked_moveset,
+                _,
+                _,
+                intangibility,
+                attacked_grabbing,
+                attacked_grabbed,
+                ..,
+            )) = character_query.get(attacked_entity)
+            else {
+                continue;
             };
 
-            if attack.has_attacked.contains(&attacked_entity) {
+            // Each kind of attack keeps track of who it hit on its own
+            let attacker_controller = attacker
+                .and_then(|attacker| character_query.get(attacker).ok())
+                .map(|(_, controller, ..)| controller);
+            let (can_hit, first_connection) = match (&projectile, &hazard, attacker_controller) {
+                (Some(_), _, _) => (
+                    !attack.has_attacked.contains(&attacked_entity),
+                    attack.has_attacked.is_empty(),
+                ),
+                (None, Some(hazard), _) => {
+                    (hazard.can_hit(attacked_entity, attack.rehit_rate), false)
+                }
+                (None, None, Some(controller)) => (
+                    controller.can_hit(attacked_entity, attack.rehit_rate),
+                    !controller.has_connected,
+                ),
+                (None, None, None) => continue,
+            };
+            if !can_hit {
+                continue;
+            }
+
+            if intangibility.map_or(false, |intangibility| !intangibility.0.finished()) {
                 continue;
             }
 
-            // Change code if stupid
-            attacked_character.percentage += attack.damage;
+            let defense = attacked_controller
+                .current_attack
+                .as_ref()
+                .and_then(|(kind, timer)| {
+                    attacked_moveset
+                        .0
+                        .get(kind)
+                        .and_then(|current_move| current_move.defense(timer.elapsed_secs()))
+                });
+            let is_shielding = attacked_movement.is_shielding;
+            let is_on_stage = attacked_movement.is_on_stage();
+            let can_be_grabbed = attacked_grabbing.is_none()
+                && attacked_grabbed.is_none()
+                && !grabs_this_frame.contains(&attacked_entity);
+
+            // Invincibility takes no hit at all, so the move can still hit once it's over
+            if matches!(defense, Some(Defense::Invincible)) {
+                continue;
+            }
+
+            match (&projectile, &mut hazard) {
+                (Some(_), _) => attack.has_attacked.push(attacked_entity),
+                (None, Some(hazard)) => hazard.track_hit(attacked_entity),
+                (None, None) => {}
+            }
+
+            // The attacker's side of the hit, hazards don't have one
+            let (attacker_percentage, attacker_velocity) = match attacker {
+                Some(attacker) => {
+                    let Ok((
+                        attacker_character,
+                        mut attacker_controller,
+                        _,
+                        _,
+                        mut attacker_stale_moves,
+                        attacker_vel,
+                        ..,
+                    )) = character_query.get_mut(attacker)
+                    else {
+                        continue;
+                    };
+
+                    if projectile.is_none() {
+                        attacker_controller.track_hit(attacked_entity);
+                    }
+
+                    if first_connection && attack.kind != HitboxKind::Grab {
+                        attacker_controller.has_connected = true;
+                        if let Some(kind) = attack.attack_kind {
+                            attacker_stale_moves.push(kind, &ruleset.stale_moves);
+                        }
+                    }
+
+                    (attacker_character.percentage, attacker_vel.linvel)
+                }
+                None => (0., Vec2::ZERO),
+            };
+
+            if attack.kind == HitboxKind::Grab {
+                let Some(attacker) = attacker else {
+                    continue;
+                };
+
+                if can_be_grabbed && !grabs_this_frame.contains(&attacker) {
+                    commands.entity(attacker).insert(Grabbing {
+                        victim: attacked_entity,
+                    });
+                    // Grabbed characters are held in place, they aren't flying anymore
+                    commands
+                        .entity(attacked_entity)
+                        .remove::<Launch>()
+                        .insert(Grabbed::new(attacker));
+                    grabs_this_frame.extend([attacker, attacked_entity]);
+                }
+                continue;
+            }
+
+            // Shields block everything but grabs
+            if is_shielding {
+                if projectile.is_some() {
+                    commands.entity(attack_entity).despawn_recursive();
+                }
+                continue;
+            }
+
+            let Ok((
+                mut attacked_character,
+                mut attacked_controller,
+                _,
+                _,
+                _,
+                mut attacked_vel,
+                _,
+                _,
+                _,
+                knockdown,
+                mut launch,
+                attacked_handicap,
+            )) = character_query.get_mut(attacked_entity)
+            else {
+                continue;
+            };
+
+            let damage = ruleset.damage_taken(attack.damage, attacked_handicap);
+            let knockback = attack.angle.apply(attack.knockback, is_on_stage)
+                * ruleset.rage_multiplier(attacker_percentage);
+
+            match defense {
+                Some(Defense::Counter {
+                    counterattack,
+                    multiplier,
+                }) => {
+                    attacked_controller.countered =
+                        Some((counterattack, attack.damage * multiplier));
+                }
+                Some(Defense::Armor { threshold }) if knockback.length() < threshold => {
+                    attacked_character.add_damage(damage);
+                }
+                Some(Defense::SuperArmor) => {
+                    attacked_character.add_damage(damage);
+                }
+                _ => {
+                    // Change code if stupid
+                    attacked_character.add_damage(damage);
+
+                    // Weak hits on a lying character force it up, but it still can't act
+                    let jab_lock = knockdown.and_then(|_| Knockdown::jab_lock(knockback));
+                    let hitstun = match jab_lock {
+                        Some(hitstun) => hitstun,
+                        None => {
+                            let launch_velocity =
+                                launch::launch_velocity(knockback, attacked_character.percentage);
+
+                            // Autolink sets the velocity relative to the attacker instead of
+                            // adding to it, so it doesn't pile up over the hits of a multi-hit
+                            let flying_velocity = if attack.angle == KnockbackAngle::Autolink {
+                                if let Some(launch) = &mut launch {
+                                    launch.remove_from(&mut attacked_vel);
+                                }
+                                // Launches are in units, velocities in pixels
+                                launch_velocity + attacker_velocity / stage.physics.pixels_per_unit
+                            } else {
+                                launch_velocity
+                            };
+
+                            match launch {
+                                Some(mut launch) => launch.relaunch(flying_velocity),
+                                None => {
+                                    commands
+                                        .entity(attacked_entity)
+                                        .insert(Launch::new(flying_velocity));
+                                }
+                            }
+                            Hitstun::from_knockback(launch_velocity)
+                        }
+                    };
+                    commands
+                        .entity(attacked_entity)
+                        .remove::<Knockdown>()
+                        .insert(hitstun);
+
+                    if let Some(effect) = attack.status {
+                        status_events.send(ApplyStatus {
+                            entity: attacked_entity,
+                            effect,
+                        });
+                    }
+                }
+            }
+
+            if let Some(projectile) = &mut projectile {
+                if projectile.hit() {
+                    commands.entity(attack_entity).despawn_recursive();
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn intangibility_system(
+    mut character: Query<(Entity, &mut Intangibility)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut intangibility) in character.iter_mut() {
+        if intangibility.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Intangibility>();
+        }
+    }
+}
+
+fn hitstun_system(
+    mut character: Query<(Entity, &mut Hitstun)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut hitstun) in character.iter_mut() {
+        if hitstun.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Hitstun>();
+        }
+    }
+}
+
+/// Ends `Helpless` when the character lands and gets rid of the landing lag once it's over
+fn helpless_system(
+    helpless_query: Query<(Entity, &Helpless, &CharacterMovement)>,
+    mut landing_lag_query: Query<(Entity, &mut LandingLag)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut landing_lag) in landing_lag_query.iter_mut() {
+        if landing_lag.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<LandingLag>();
+        }
+    }
+
+    for (entity, helpless, movement) in helpless_query.iter() {
+        if movement.is_on_stage() {
+            commands
+                .entity(entity)
+                .remove::<Helpless>()
+                .insert(LandingLag(Timer::from_seconds(
+                    helpless.landing_lag,
+                    TimerMode::Once,
+                )));
+        }
+    }
+}
+
+/// What gets reset when a character comes back, after being KO'd or for another game
+#[derive(WorldQuery)]
+#[world_query(mutable)]
+pub struct CharacterReset {
+    pub entity: Entity,
+    pub transform: &'static mut Transform,
+    pub velocity: &'static mut Velocity,
+    pub gravity: &'static mut GravityScale,
+    pub character: &'static mut Character,
+    pub movement: &'static CharacterMovement,
+    pub statuses: &'static mut StatusEffects,
+    pub handicap: &'static Handicap,
+}
 
-            attacked_controller.velocity_from_knockback += attack.knockback;
+impl CharacterResetItem<'_> {
+    /// Puts the character at `position` the way it started, without damage, statuses
+    /// or a launch, and not holding or held by anything
+    pub fn reset(
+        &mut self,
+        position: Vec2,
+        commands: &mut Commands,
+        expired_events: &mut EventWriter<StatusExpired>,
+    ) {
+        self.transform.translation = position.extend(self.transform.translation.z);
+        self.velocity.linvel = Vec2::ZERO;
+        // Hanging and being grabbed turn gravity off
+        self.gravity.0 = self.movement.normal_gravity;
+        self.character.reset_damage(self.handicap);
+        self.statuses.expire_all(self.entity, expired_events);
+        // Whoever was grabbing or grabbed by the character lets go on their own
+        commands.entity(self.entity).remove::<(
+            Launch,
+            Hitstun,
+            Helpless,
+            LandingLag,
+            Knockdown,
+            Grabbed,
+            Grabbing,
+            LedgeHang,
+            Intangibility,
+        )>();
+    }
+}
 
-            attack.has_attacked.push(attacked_entity);
+/// Characters leaving the blast zone are KO'd and come back at a respawn point
+fn blast_zone_system(
+    mut character_query: Query<CharacterReset>,
+    stage: Res<StageFile>,
+    mut respawns: Local<usize>,
+    mut commands: Commands,
+    mut expired_events: EventWriter<StatusExpired>,
+) {
+    for mut character in character_query.iter_mut() {
+        if stage
+            .blast_zone
+            .contains(character.transform.translation.truncate())
+        {
+            continue;
         }
+
+        character.reset(
+            stage.respawn_point(*respawns),
+            &mut commands,
+            &mut expired_events,
+        );
+        *respawns += 1;
     }
 }
 
+fn spawn_hitbox(
+    commands: &mut Commands,
+    hitbox: &Hitbox,
+    attack_kind: AttackKind,
+    damage: f32,
+    stale_multiplier: f32,
+    rehit_rate: Option<f32>,
+    facing: f32,
+) -> Entity {
+    let mirror = Vec2::new(facing, 1.);
+
+    commands
+        .spawn((
+            TransformBundle::from_transform(Transform::from_translation(
+                (hitbox.offset * mirror).extend(0.),
+            )),
+            Collider::cuboid(hitbox.half_extents.x, hitbox.half_extents.y),
+            Sensor,
+            CharacterAttack {
+                damage: damage * stale_multiplier,
+                knockback: hitbox.knockback * mirror * stale_multiplier,
+                kind: hitbox.kind,
+                transcendent: hitbox.transcendent,
+                angle: hitbox.angle,
+                rehit_rate,
+                status: hitbox.status,
+                attack_kind: Some(attack_kind),
+                ..default()
+            },
+            ActiveEvents::COLLISION_EVENTS,
+            match hitbox.kind {
+                HitboxKind::Attack => ColliderKind::Attack,
+                HitboxKind::Grab => ColliderKind::Grab,
+                HitboxKind::Reflect | HitboxKind::Absorb => ColliderKind::Reflector,
+            }
+            .collision_groups(),
+        ))
+        .id()
+}
+
+/// Starts, plays and ends the characters' moves
 fn character_attack(
-    mut character: Query<(Entity, &mut CharacterAttackController)>,
-    children: Query<(Entity, &Parent), With<CharacterAttack>>,
+    mut character: Query<(
+        Entity,
+        &GlobalTransform,
+        &mut CharacterAttackController,
+        &CharacterMovement,
+        &Moveset,
+        &StaleMoves,
+        &mut Velocity,
+        &mut LedgeGrabber,
+        Option<&LedgeHang>,
+        Option<&Grabbing>,
+        Option<&Grabbed>,
+        (
+            Option<&Helpless>,
+            Option<&LandingLag>,
+            Option<&Rebound>,
+            Option<&Hitstun>,
+            Option<&Knockdown>,
+            &StatusEffects,
+        ),
+    )>,
+    ruleset: Res<Ruleset>,
+    stage: Res<StageFile>,
     time: Res<Time>,
     mut commands: Commands,
 ) {
-    for (entity, mut character) in character.iter_mut() {
+    let scale = stage.physics.pixels_per_unit;
+
+    for (
+        entity,
+        tf,
+        mut character,
+        movement,
+        moveset,
+        stale_moves,
+        mut vel,
+        mut grabber,
+        hang,
+        grabbing,
+        grabbed,
+        (helpless, landing_lag, rebound, hitstun, knockdown, statuses),
+    ) in character.iter_mut()
+    {
+        let character = &mut *character;
         let mut just_finished_attack = false;
 
-        if let Some(timer) = &mut character.attack_timer {
-            just_finished_attack = timer.finished();
+        if let Some((_, timer)) = &mut character.current_attack {
             timer.tick(time.delta());
+            just_finished_attack = timer.finished();
+        }
+
+        // Platform cancel
+        if character.is_attacking() && character.is_aerial_attack && movement.is_on_soft_platform()
+        {
+            just_finished_attack = true;
+        }
+
+        // Grabbing a ledge, getting grabbed, clanking, getting hit or a status effect
+        // ends whatever the character was doing
+        if character.is_attacking()
+            && (hang.is_some()
+                || grabbed.is_some()
+                || rebound.is_some()
+                || hitstun.is_some()
+                || statuses.is_disabled())
+        {
+            just_finished_attack = true;
+        }
+
+        // The counter caught a hit, the counterattack replaces it
+        if character.is_attacking() && character.countered.is_some() {
+            just_finished_attack = true;
+        }
+
+        // The grab connected, the grab system takes it from here
+        if grabbing.is_some() && matches!(character.current_attack, Some((AttackKind::Grab, _))) {
+            just_finished_attack = true;
         }
 
         if just_finished_attack {
-            character.attack_timer = None;
+            let (kind, _) = character.current_attack.take().unwrap();
 
-            for (child, parent) in children.iter() {
-                if entity == parent.get() {
-                    commands.entity(child).despawn_recursive();
-                    break;
+            for (_, hitbox) in character.active_hitboxes.drain(..) {
+                commands.entity(hitbox).despawn_recursive();
+            }
+            grabber.set_tether_reach(None);
+
+            if let Some(landing_lag) = moveset
+                .0
+                .get(&kind)
+                .and_then(|finished_move| finished_move.helpless_landing_lag)
+            {
+                if movement.is_not_touching_stage() && hang.is_none() {
+                    commands.entity(entity).insert(Helpless { landing_lag });
                 }
             }
-            break;
+
+            if let Some((counterattack, _)) = character.countered {
+                character.wants_to_attack = Some(counterattack);
+            }
+            continue;
         }
 
-        if character.is_attacking() {
-            character.wants_to_forward_air = false;
+        // The ledge and knockdown systems decide what happens to `wants_to_attack`
+        if hang.is_some() || knockdown.is_some() {
             continue;
         }
 
-        if character.wants_to_forward_air {
-            character.attack_timer = Some(Timer::from_seconds(1., TimerMode::Once));
-
-            let attack_entity = commands
-                .spawn((
-                    Collider::cuboid(100., 100.),
-                    Sensor,
-                    CharacterAttack {
-                        damage: 20.,
-                        knockback: Vec2::splat(500.),
-                        ..default()
-                    },
-                    ActiveEvents::COLLISION_EVENTS,
-                    CollisionGroups::new(
-                        Group::from_bits(0b100).unwrap(),
-                        Group::from_bits(0b10).unwrap(),
-                    ),
-                ))
-                .id();
-
-            commands.entity(entity).add_child(attack_entity);
+        if let Some((kind, timer)) = &character.current_attack {
+            character.wants_to_attack = None;
+
+            // The move can be gone from the moveset if it was edited, then it just ends
+            let Some(current_move) = moveset.0.get(kind) else {
+                character.current_attack = None;
+                for (_, hitbox) in character.active_hitboxes.drain(..) {
+                    commands.entity(hitbox).despawn_recursive();
+                }
+                continue;
+            };
+            let elapsed = timer.elapsed_secs();
+
+            for (index, hitbox) in current_move.hitboxes.iter().enumerate() {
+                let spawned = character
+                    .active_hitboxes
+                    .iter()
+                    .position(|(spawned_index, _)| *spawned_index == index);
+
+                match (current_move.is_hitbox_active(index, elapsed), spawned) {
+                    (true, None) => {
+                        let hitbox_entity = spawn_hitbox(
+                            &mut commands,
+                            hitbox,
+                            *kind,
+                            character.counter_damage.unwrap_or(hitbox.damage),
+                            character.stale_multiplier,
+                            current_move.rehit_rate,
+                            movement.facing,
+                        );
+                        if hitbox.launcher {
+                            character.hit_tracking.clear();
+                        }
+                        commands.entity(entity).add_child(hitbox_entity);
+                        character.active_hitboxes.push((index, hitbox_entity));
+                    }
+                    (false, Some(position)) => {
+                        let (_, hitbox_entity) = character.active_hitboxes.remove(position);
+                        commands.entity(hitbox_entity).despawn_recursive();
+                    }
+                    _ => {}
+                }
+            }
+
+            for spawn in current_move
+                .projectiles
+                .iter()
+                .skip(character.projectiles_thrown)
+                .take_while(|spawn| spawn.time <= elapsed)
+            {
+                // Projectile speeds are in units
+                let spawn = ProjectileSpawn {
+                    velocity: spawn.velocity * scale,
+                    ..spawn.clone()
+                };
+                projectile::spawn_projectile(
+                    &mut commands,
+                    &spawn,
+                    *kind,
+                    character.stale_multiplier,
+                    entity,
+                    tf.translation().truncate(),
+                    movement.facing,
+                );
+                character.projectiles_thrown += 1;
+            }
+
+            if let Some(recovery) = &current_move.recovery {
+                grabber.set_tether_reach(recovery.tether_reach(elapsed));
+
+                if let Some(stage) = recovery.hover_stage(elapsed) {
+                    vel.linvel = Vec2::new(
+                        stage.velocity.x * movement.facing + movement.x * stage.drift,
+                        stage.velocity.y,
+                    ) * scale;
+                }
+            }
+            continue;
+        }
+
+        let Some(kind) = character.wants_to_attack.take() else {
+            continue;
+        };
+        let countered = character.countered.take();
+
+        if helpless.is_some()
+            || landing_lag.is_some()
+            || grabbed.is_some()
+            || rebound.is_some()
+            || hitstun.is_some()
+            || statuses.is_disabled()
+        {
+            continue;
+        }
+
+        let kind = match kind {
+            AttackKind::Grab if !movement.is_on_stage() => AttackKind::ZAir,
+            kind => kind,
+        };
+
+        let Some(new_move) = moveset.0.get(&kind) else {
+            continue;
+        };
+
+        character.current_attack = Some((
+            kind,
+            Timer::from_seconds(new_move.duration, TimerMode::Once),
+        ));
+        character.is_aerial_attack = !movement.is_on_stage();
+        character.projectiles_thrown = 0;
+        character.hit_tracking.clear();
+        character.has_connected = false;
+        character.stale_multiplier = stale_moves.multiplier(kind, &ruleset.stale_moves);
+        character.counter_damage = match countered {
+            Some((counterattack, damage)) if counterattack == kind => Some(damage),
+            _ => None,
+        };
+
+        if let Some(impulse) = new_move
+            .recovery
+            .as_ref()
+            .and_then(|recovery| recovery.impulse.as_ref())
+        {
+            let angle = movement.x * impulse.angle_control;
+            vel.linvel = Vec2::new(angle.sin(), angle.cos()) * impulse.speed * scale;
         }
     }
 }
@@ -0,0 +1,588 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use super::status::StatusEffect;
+
+/// How far up the Sakurai angle sends characters in the air, in radians
+const SAKURAI_AIR_ANGLE: f32 = 0.663;
+/// The highest the Sakurai angle sends characters on the stage, in radians
+const SAKURAI_GROUND_MAX_ANGLE: f32 = 0.698;
+/// Below this much knockback, the Sakurai angle sends characters on the stage flat
+const SAKURAI_GROUND_MIN_KNOCKBACK: f32 = 600.;
+/// At this much knockback, the Sakurai angle sends characters on the stage at its highest
+const SAKURAI_GROUND_MAX_KNOCKBACK: f32 = 900.;
+/// How much of the knockback is left when a spike bounces off the stage
+const GROUND_BOUNCE_FACTOR: f32 = 0.8;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AttackKind {
+    ForwardAir,
+    /// The attack done when getting up from a ledge
+    LedgeAttack,
+    /// The attack done when getting up after being knocked down
+    GetupAttack,
+    UpSpecial,
+    SideSpecial,
+    Grab,
+    /// A grab done in the air, usually a tether
+    ZAir,
+    Pummel,
+    ForwardThrow,
+    BackThrow,
+    UpThrow,
+    DownThrow,
+}
+
+/// Everything a character can do when attacking
+#[derive(Serialize, Deserialize, Component, Debug, Clone)]
+pub struct Moveset(pub HashMap<AttackKind, Move>);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Move {
+    /// How long the move lasts, in seconds
+    pub duration: f32,
+    pub hitboxes: Vec<Hitbox>,
+
+    /// How long after hitting someone the move can hit them again, in seconds.
+    /// Without it, each character can only be hit once per move
+    pub rehit_rate: Option<f32>,
+
+    /// If set, the character is left `Helpless` when the move ends in the air,
+    /// and landing afterwards has this much landing lag, in seconds
+    pub helpless_landing_lag: Option<f32>,
+
+    pub recovery: Option<Recovery>,
+
+    /// Only for moves done while holding someone
+    pub throw: Option<Throw>,
+
+    /// In the order they're thrown
+    #[serde(default)]
+    pub projectiles: Vec<ProjectileSpawn>,
+
+    /// How the character is protected from hits during the move
+    #[serde(default)]
+    pub defense: Vec<DefenseWindow>,
+}
+
+/// A hitbox of a move. Positions and knockback are
+/// for a character facing right, they get mirrored when facing left
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Hitbox {
+    /// When the hitbox comes out, in seconds since the start of the move
+    pub start: f32,
+    /// When the hitbox goes away, in seconds since the start of the move
+    pub end: f32,
+
+    pub offset: Vec2,
+    pub half_extents: Vec2,
+    pub damage: f32,
+    pub knockback: Vec2,
+
+    #[serde(default)]
+    pub kind: HitboxKind,
+
+    /// Transcendent hitboxes never clank with other hitboxes
+    #[serde(default)]
+    pub transcendent: bool,
+
+    /// The last hit of a multi-hit move. When it comes out, the move
+    /// forgets who it hit, so everyone caught by the move gets launched
+    #[serde(default)]
+    pub launcher: bool,
+
+    #[serde(default)]
+    pub angle: KnockbackAngle,
+
+    /// Given to the characters the hitbox hits
+    #[serde(default)]
+    pub status: Option<StatusEffect>,
+}
+
+/// How the direction of the knockback is picked
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KnockbackAngle {
+    /// The knockback is used as it is. Pointing down, it's a meteor smash
+    #[default]
+    Fixed,
+    /// The 361 angle, only the length and horizontal direction of the knockback are used.
+    /// Flat for weak hits on the stage, diagonal otherwise
+    Sakurai,
+    /// Carries the hit character along with the attacker,
+    /// so the next hits of a multi-hit move connect.
+    /// The knockback is relative to the attacker's velocity
+    Autolink,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HitboxKind {
+    /// Hits characters
+    #[default]
+    Attack,
+    /// Catches characters instead of hitting them, and goes through shields
+    Grab,
+    /// Sends projectiles back, they belong to the reflector afterwards
+    Reflect,
+    /// Takes projectiles in, healing as much as they would have hurt
+    Absorb,
+}
+
+/// A projectile thrown by a move. Like hitboxes, everything
+/// is for a character facing right and gets mirrored when facing left
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProjectileSpawn {
+    /// When the projectile is thrown, in seconds since the start of the move
+    pub time: f32,
+
+    pub offset: Vec2,
+    pub half_extents: Vec2,
+    pub velocity: Vec2,
+    pub gravity: f32,
+    /// How long the projectile lasts if it doesn't hit anything, in seconds
+    pub lifetime: f32,
+    /// How many characters the projectile can hit before it's gone
+    pub hits: usize,
+
+    pub damage: f32,
+    pub knockback: Vec2,
+}
+
+/// A part of a move during which hits are treated differently
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DefenseWindow {
+    pub start: f32,
+    pub end: f32,
+    pub defense: Defense,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum Defense {
+    /// Takes damage, but ignores knockback weaker than `threshold`
+    Armor { threshold: f32 },
+    /// Takes damage, but never knockback
+    SuperArmor,
+    /// Hits are caught and answered with `counterattack`, whose
+    /// hitboxes deal the caught hit's damage times `multiplier`
+    Counter {
+        counterattack: AttackKind,
+        multiplier: f32,
+    },
+    /// Hits connect, but do nothing
+    Invincible,
+}
+
+/// Lets go of the grabbed character with some damage and knockback.
+/// The knockback is for a character facing right, like hitboxes
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Throw {
+    /// When the grabbed character is let go, in seconds since the start of the move
+    pub release: f32,
+    pub damage: f32,
+    pub knockback: Vec2,
+}
+
+/// The parts of a move that get the character back to the stage.
+/// They can be combined, like a tether on a move that also hovers
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Recovery {
+    pub impulse: Option<RecoveryImpulse>,
+    pub tether: Option<Tether>,
+
+    /// Played one after the other, starting with the move
+    pub hover: Vec<HoverStage>,
+}
+
+/// Launches the character when the move starts
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RecoveryImpulse {
+    pub speed: f32,
+    /// How far from straight up, in radians, the stick can angle the impulse
+    pub angle_control: f32,
+}
+
+/// Grabs ledges from further away while it's out
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Tether {
+    pub start: f32,
+    pub end: f32,
+    pub reach: f32,
+}
+
+/// Keeps the character moving at `velocity` for `duration` seconds
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HoverStage {
+    pub duration: f32,
+    pub velocity: Vec2,
+    /// How much the stick changes the horizontal velocity
+    pub drift: f32,
+}
+
+impl Move {
+    /// Whether `hitbox` should be out `elapsed` seconds into the move
+    pub fn is_hitbox_active(&self, hitbox: usize, elapsed: f32) -> bool {
+        let hitbox = &self.hitboxes[hitbox];
+        (hitbox.start..hitbox.end).contains(&elapsed)
+    }
+
+    /// How the character is protected `elapsed` seconds into the move
+    pub fn defense(&self, elapsed: f32) -> Option<Defense> {
+        self.defense
+            .iter()
+            .find(|window| (window.start..window.end).contains(&elapsed))
+            .map(|window| window.defense)
+    }
+}
+
+impl KnockbackAngle {
+    /// The knockback a hit actually gives. Autolink adds the attacker's velocity on top,
+    /// when the character gets launched
+    pub fn apply(self, knockback: Vec2, grounded: bool) -> Vec2 {
+        let knockback = match self {
+            KnockbackAngle::Fixed | KnockbackAngle::Autolink => knockback,
+            KnockbackAngle::Sakurai => {
+                let strength = knockback.length();
+                let angle = if grounded {
+                    let t = ((strength - SAKURAI_GROUND_MIN_KNOCKBACK)
+                        / (SAKURAI_GROUND_MAX_KNOCKBACK - SAKURAI_GROUND_MIN_KNOCKBACK))
+                        .clamp(0., 1.);
+                    t * SAKURAI_GROUND_MAX_ANGLE
+                } else {
+                    SAKURAI_AIR_ANGLE
+                };
+                Vec2::new(knockback.x.signum() * angle.cos(), angle.sin()) * strength
+            }
+        };
+
+        // Spikes can't send characters through the stage, so they bounce off it
+        if grounded && knockback.y < 0. {
+            ground_bounce(knockback)
+        } else {
+            knockback
+        }
+    }
+}
+
+/// The velocity a character sent down with `knockback` bounces off the stage with
+pub fn ground_bounce(knockback: Vec2) -> Vec2 {
+    Vec2::new(knockback.x, -knockback.y) * GROUND_BOUNCE_FACTOR
+}
+
+impl Recovery {
+    /// The hover stage that is playing `elapsed` seconds into the move
+    pub fn hover_stage(&self, elapsed: f32) -> Option<&HoverStage> {
+        let mut stage_start = 0.;
+        for stage in self.hover.iter() {
+            if elapsed < stage_start + stage.duration {
+                return Some(stage);
+            }
+            stage_start += stage.duration;
+        }
+        None
+    }
+
+    /// How far ledges can be grabbed from `elapsed` seconds into the move
+    pub fn tether_reach(&self, elapsed: f32) -> Option<f32> {
+        self.tether
+            .as_ref()
+            .filter(|tether| (tether.start..tether.end).contains(&elapsed))
+            .map(|tether| tether.reach)
+    }
+}
+
+impl Default for Moveset {
+    /// Bandana Dee's moves
+    fn default() -> Self {
+        Self(HashMap::from_iter([
+            (
+                AttackKind::ForwardAir,
+                Move {
+                    duration: 1.,
+                    hitboxes: vec![Hitbox {
+                        start: 0.,
+                        end: 1.,
+                        offset: Vec2::ZERO,
+                        half_extents: Vec2::splat(100.),
+                        damage: 20.,
+                        knockback: Vec2::splat(500.),
+                        ..default()
+                    }],
+                    ..default()
+                },
+            ),
+            (
+                AttackKind::LedgeAttack,
+                Move {
+                    duration: 0.5,
+                    hitboxes: vec![Hitbox {
+                        start: 0.1,
+                        end: 0.3,
+                        offset: Vec2::new(50., 0.),
+                        half_extents: Vec2::new(100., 50.),
+                        damage: 9.,
+                        knockback: Vec2::new(600., 300.),
+                        ..default()
+                    }],
+                    ..default()
+                },
+            ),
+            (
+                AttackKind::GetupAttack,
+                Move {
+                    duration: 0.5,
+                    hitboxes: vec![
+                        Hitbox {
+                            start: 0.1,
+                            end: 0.2,
+                            offset: Vec2::new(60., -20.),
+                            half_extents: Vec2::new(60., 30.),
+                            damage: 7.,
+                            knockback: Vec2::new(500., 200.),
+                            ..default()
+                        },
+                        Hitbox {
+                            start: 0.2,
+                            end: 0.3,
+                            offset: Vec2::new(-60., -20.),
+                            half_extents: Vec2::new(60., 30.),
+                            damage: 7.,
+                            knockback: Vec2::new(-500., 200.),
+                            ..default()
+                        },
+                    ],
+                    ..default()
+                },
+            ),
+            (
+                // Spear Copter
+                AttackKind::UpSpecial,
+                Move {
+                    duration: 1.2,
+                    hitboxes: vec![
+                        Hitbox {
+                            start: 0.1,
+                            end: 1.,
+                            offset: Vec2::new(0., 60.),
+                            half_extents: Vec2::new(80., 30.),
+                            damage: 1.,
+                            knockback: Vec2::new(0., 50.),
+                            angle: KnockbackAngle::Autolink,
+                            ..default()
+                        },
+                        Hitbox {
+                            start: 1.,
+                            end: 1.1,
+                            offset: Vec2::new(0., 60.),
+                            half_extents: Vec2::new(90., 40.),
+                            damage: 3.,
+                            knockback: Vec2::new(0., 600.),
+                            launcher: true,
+                            ..default()
+                        },
+                    ],
+                    rehit_rate: Some(0.15),
+                    helpless_landing_lag: Some(0.4),
+                    recovery: Some(Recovery {
+                        impulse: None,
+                        tether: None,
+                        hover: vec![
+                            HoverStage {
+                                duration: 0.4,
+                                velocity: Vec2::new(0., 700.),
+                                drift: 150.,
+                            },
+                            HoverStage {
+                                duration: 0.4,
+                                velocity: Vec2::new(0., 400.),
+                                drift: 200.,
+                            },
+                            HoverStage {
+                                duration: 0.4,
+                                velocity: Vec2::new(0., 150.),
+                                drift: 250.,
+                            },
+                        ],
+                    }),
+                    ..default()
+                },
+            ),
+            (
+                // Spear Throw
+                AttackKind::SideSpecial,
+                Move {
+                    duration: 0.6,
+                    projectiles: vec![ProjectileSpawn {
+                        time: 0.2,
+                        offset: Vec2::new(60., 10.),
+                        half_extents: Vec2::new(40., 8.),
+                        velocity: Vec2::new(1200., 0.),
+                        gravity: 300.,
+                        lifetime: 1.5,
+                        hits: 1,
+                        damage: 8.,
+                        knockback: Vec2::new(500., 250.),
+                    }],
+                    ..default()
+                },
+            ),
+            (
+                AttackKind::Grab,
+                Move {
+                    duration: 0.5,
+                    hitboxes: vec![Hitbox {
+                        start: 0.1,
+                        end: 0.25,
+                        offset: Vec2::new(60., 0.),
+                        half_extents: Vec2::splat(40.),
+                        kind: HitboxKind::Grab,
+                        ..default()
+                    }],
+                    ..default()
+                },
+            ),
+            (
+                AttackKind::ZAir,
+                Move {
+                    duration: 0.6,
+                    hitboxes: vec![Hitbox {
+                        start: 0.1,
+                        end: 0.4,
+                        offset: Vec2::new(120., 0.),
+                        half_extents: Vec2::new(80., 15.),
+                        damage: 4.,
+                        knockback: Vec2::new(300., 100.),
+                        ..default()
+                    }],
+                    recovery: Some(Recovery {
+                        tether: Some(Tether {
+                            start: 0.1,
+                            end: 0.4,
+                            reach: 200.,
+                        }),
+                        ..default()
+                    }),
+                    ..default()
+                },
+            ),
+            (
+                AttackKind::Pummel,
+                Move {
+                    duration: 0.3,
+                    hitboxes: vec![Hitbox {
+                        start: 0.05,
+                        end: 0.1,
+                        offset: Vec2::new(60., 0.),
+                        half_extents: Vec2::splat(30.),
+                        damage: 1.5,
+                        ..default()
+                    }],
+                    ..default()
+                },
+            ),
+            (
+                AttackKind::ForwardThrow,
+                Move {
+                    duration: 0.5,
+                    throw: Some(Throw {
+                        release: 0.25,
+                        damage: 8.,
+                        knockback: Vec2::new(700., 400.),
+                    }),
+                    ..default()
+                },
+            ),
+            (
+                AttackKind::BackThrow,
+                Move {
+                    duration: 0.6,
+                    throw: Some(Throw {
+                        release: 0.3,
+                        damage: 9.,
+                        knockback: Vec2::new(-800., 400.),
+                    }),
+                    ..default()
+                },
+            ),
+            (
+                AttackKind::UpThrow,
+                Move {
+                    duration: 0.5,
+                    throw: Some(Throw {
+                        release: 0.3,
+                        damage: 7.,
+                        knockback: Vec2::new(0., 900.),
+                    }),
+                    ..default()
+                },
+            ),
+            (
+                AttackKind::DownThrow,
+                Move {
+                    duration: 0.4,
+                    throw: Some(Throw {
+                        release: 0.2,
+                        damage: 6.,
+                        knockback: Vec2::new(200., 600.),
+                    }),
+                    ..default()
+                },
+            ),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.abs_diff_eq(b, 1e-3), "{a} != {b}");
+    }
+
+    #[test]
+    fn fixed_knockback_is_used_as_it_is() {
+        let knockback = Vec2::new(300., 400.);
+        assert_close(KnockbackAngle::Fixed.apply(knockback, true), knockback);
+        assert_close(KnockbackAngle::Fixed.apply(knockback, false), knockback);
+    }
+
+    #[test]
+    fn spikes_bounce_off_the_stage() {
+        let knockback = Vec2::new(100., -500.);
+        assert_close(
+            KnockbackAngle::Fixed.apply(knockback, true),
+            Vec2::new(100., 500.) * GROUND_BOUNCE_FACTOR,
+        );
+        // In the air they're meteor smashes
+        assert_close(KnockbackAngle::Fixed.apply(knockback, false), knockback);
+    }
+
+    #[test]
+    fn sakurai_angle_keeps_the_strength_and_direction() {
+        let knockback = Vec2::new(-1000., 0.);
+        let applied = KnockbackAngle::Sakurai.apply(knockback, false);
+
+        assert!((applied.length() - 1000.).abs() < 1e-3);
+        assert!(applied.x < 0.);
+        assert!((applied.y.atan2(-applied.x) - SAKURAI_AIR_ANGLE).abs() < 1e-4);
+    }
+
+    #[test]
+    fn sakurai_angle_on_the_stage_goes_from_flat_to_diagonal() {
+        let weak = KnockbackAngle::Sakurai.apply(Vec2::X * SAKURAI_GROUND_MIN_KNOCKBACK, true);
+        assert_close(weak, Vec2::X * SAKURAI_GROUND_MIN_KNOCKBACK);
+
+        let strong = KnockbackAngle::Sakurai.apply(Vec2::X * SAKURAI_GROUND_MAX_KNOCKBACK, true);
+        assert!((strong.y.atan2(strong.x) - SAKURAI_GROUND_MAX_ANGLE).abs() < 1e-4);
+
+        // Stronger than the max doesn't go any higher
+        let stronger =
+            KnockbackAngle::Sakurai.apply(Vec2::X * SAKURAI_GROUND_MAX_KNOCKBACK * 2., true);
+        assert!((stronger.y.atan2(stronger.x) - SAKURAI_GROUND_MAX_ANGLE).abs() < 1e-4);
+    }
+
+    #[test]
+    fn autolink_knockback_is_left_for_the_launch() {
+        let knockback = Vec2::new(50., 20.);
+        assert_close(KnockbackAngle::Autolink.apply(knockback, false), knockback);
+    }
+}
@@ -2,13 +2,20 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use leafwing_input_manager::prelude::*;
 
-use crate::camera::CameraFollows;
+use crate::{camera::CameraFollows, layers::ColliderKind, stage::StageFile};
 
-use super::{CharacterAttackController, CharacterBundle, CharacterMovement};
+use super::{
+    grab::grab_system, status::status_system, Character, CharacterAttackController,
+    CharacterBundle, CharacterFile, CharacterMovement,
+};
+
+const PLAYER_CHARACTER_FILE: &str = "assets/characters/bandana_dee.json";
 
 const FASTFALL_THRESHOLD: f32 = 0.5;
 // How fast you need to move the stick to fastfall
 const STICK_MOVEMENT_NEEDED_TO_FASTFALL: f32 = 0.1;
+// How far down the stick must be to drop through soft platforms
+const DROP_THROUGH_THRESHOLD: f32 = 0.7;
 
 pub struct PlayerPlugin;
 
@@ -17,7 +24,8 @@ impl Plugin for PlayerPlugin {
         app.add_startup_system(setup_player)
             .add_startup_system(setup_dummy)
             .add_plugin(InputManagerPlugin::<PlayerActions>::default())
-            .add_system(player_movement);
+            // So the grab and status systems see the inputs before they're used for anything else
+            .add_system(player_movement.before(grab_system).before(status_system));
     }
 }
 
@@ -35,16 +43,22 @@ enum PlayerActions {
     NormalAttack,
     SpecialAttack,
     FastFall,
+    Shield,
+    Grab,
 }
 
-fn setup_player(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup_player(mut commands: Commands, asset_server: Res<AssetServer>, stage: Res<StageFile>) {
+    let file = CharacterFile::load(PLAYER_CHARACTER_FILE).unwrap_or_else(|error| {
+        error!("{error}, using a default character");
+        CharacterFile::default()
+    });
     let character = CharacterBundle {
-        grav: GravityScale(20.),
-        damping: Damping {
-            linear_damping: 1.,
-            ..Default::default()
-        },
-        name: Name::new("Bandana dee (Player)"),
+        grav: GravityScale(file.movement.normal_gravity),
+        name: Name::new(format!("{} (Player)", file.name)),
+        movement: file.movement,
+        abilities: file.abilities,
+        moveset: file.moveset,
+        grab_hand: file.grab_hand,
         ..default()
     };
 
@@ -55,6 +69,7 @@ fn setup_player(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ..default()
             },
             texture: asset_server.load("bandanadee.png"),
+            transform: Transform::from_translation(stage.spawn_point(0).extend(0.)),
             ..default()
         },
         RigidBody::Dynamic,
@@ -63,10 +78,7 @@ fn setup_player(mut commands: Commands, asset_server: Res<AssetServer>) {
         LockedAxes::ROTATION_LOCKED,
         character,
         CameraFollows { padding: 250 },
-        CollisionGroups::new(
-            Group::from_bits(0b10).unwrap(),
-            Group::from_bits(0b1).unwrap(),
-        ),
+        ColliderKind::Character.collision_groups(),
     ));
 
     commands
@@ -79,24 +91,32 @@ fn setup_player(mut commands: Commands, asset_server: Res<AssetServer>) {
                 (KeyCode::S, PlayerActions::FastFall),
                 (KeyCode::A, PlayerActions::MoveLeft),
                 (KeyCode::D, PlayerActions::MoveRight),
+                (KeyCode::LShift, PlayerActions::Shield),
+                (KeyCode::K, PlayerActions::SpecialAttack),
+                (KeyCode::L, PlayerActions::Grab),
             ])
             .insert(DualAxis::left_stick(), PlayerActions::MoveStick)
             .insert(GamepadButtonType::West, PlayerActions::Jump)
             .insert(GamepadButtonType::North, PlayerActions::Jump)
             .insert(GamepadButtonType::East, PlayerActions::NormalAttack)
+            .insert(GamepadButtonType::South, PlayerActions::SpecialAttack)
+            .insert(GamepadButtonType::RightTrigger, PlayerActions::Shield)
+            .insert(GamepadButtonType::LeftTrigger, PlayerActions::Grab)
             .build(),
         })
         .insert(Player);
 }
 
-fn setup_dummy(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup_dummy(mut commands: Commands, asset_server: Res<AssetServer>, stage: Res<StageFile>) {
+    let movement = CharacterMovement::default();
     let character = CharacterBundle {
-        grav: GravityScale(20.),
-        damping: Damping {
-            linear_damping: 1.,
-            ..Default::default()
+        typ: Character {
+            slot: 1,
+            ..default()
         },
+        grav: GravityScale(movement.normal_gravity),
         name: Name::new("Bandana dee (dummy)"),
+        movement,
         ..default()
     };
 
@@ -107,6 +127,7 @@ fn setup_dummy(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ..default()
             },
             texture: asset_server.load("bandanadee.png"),
+            transform: Transform::from_translation(stage.spawn_point(1).extend(0.)),
             ..default()
         },
         RigidBody::Dynamic,
@@ -114,10 +135,7 @@ fn setup_dummy(mut commands: Commands, asset_server: Res<AssetServer>) {
         LockedAxes::ROTATION_LOCKED,
         character,
         CameraFollows { padding: 250 },
-        CollisionGroups::new(
-            Group::from_bits(0b10).unwrap(),
-            Group::from_bits(0b101).unwrap(),
-        ),
+        ColliderKind::Character.collision_groups(),
     ));
 }
 
@@ -147,6 +165,12 @@ fn player_movement(
         {
             movement.fastfall();
         }
+
+        // Drop through soft platforms
+        if axis_pair.y() < -DROP_THROUGH_THRESHOLD {
+            movement.drop_through();
+            movement.crouch();
+        }
     } else {
         movement.x = 0.;
     }
@@ -167,14 +191,39 @@ fn player_movement(
     if action_state.pressed(PlayerActions::FastFall) && *last_stick_position >= 0. {
         movement.fastfall();
     }
+    if action_state.pressed(PlayerActions::FastFall) {
+        movement.drop_through();
+        movement.crouch();
+    }
 
     // Jump
     if action_state.just_pressed(PlayerActions::Jump) {
         movement.jump()
     }
+    if action_state.pressed(PlayerActions::Jump) {
+        movement.hold_jump()
+    }
+
+    // Shield
+    if action_state.pressed(PlayerActions::Shield) {
+        movement.shield();
+    }
+    if action_state.just_pressed(PlayerActions::Shield) {
+        movement.tech();
+    }
 
     // Attack
     if action_state.just_pressed(PlayerActions::NormalAttack) {
         attack.forward_air();
     }
+    if action_state.just_pressed(PlayerActions::SpecialAttack) {
+        if movement.x != 0. {
+            attack.side_special();
+        } else {
+            attack.up_special();
+        }
+    }
+    if action_state.just_pressed(PlayerActions::Grab) {
+        attack.grab();
+    }
 }
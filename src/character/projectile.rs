@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    layers::ColliderKind,
+    stage::{Stage, StageFile},
+};
+
+use super::{
+    moves::{AttackKind, HitboxKind, ProjectileSpawn},
+    Character, CharacterAttack,
+};
+
+/// How much stronger projectiles get when reflected
+const REFLECT_DAMAGE_MULTIPLIER: f32 = 1.5;
+/// How much faster projectiles get when reflected
+const REFLECT_SPEED_MULTIPLIER: f32 = 1.2;
+
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(projectile_movement)
+            .add_system(projectile_collisions);
+    }
+}
+
+/// A hitbox that moves on its own. It belongs to `owner`,
+/// and `attack_system` treats it like one of the owner's hitboxes
+#[derive(Component, Debug, Clone)]
+pub struct Projectile {
+    pub owner: Entity,
+    gravity: f32,
+    lifetime: Timer,
+    hits_left: usize,
+}
+
+impl Projectile {
+    /// Uses up one of the hits, returns whether the projectile should be gone
+    pub fn hit(&mut self) -> bool {
+        self.hits_left = self.hits_left.saturating_sub(1);
+        self.hits_left == 0
+    }
+}
+
+pub fn spawn_projectile(
+    commands: &mut Commands,
+    spawn: &ProjectileSpawn,
+    attack_kind: AttackKind,
+    stale_multiplier: f32,
+    owner: Entity,
+    position: Vec2,
+    facing: f32,
+) {
+    let mirror = Vec2::new(facing, 1.);
+
+    commands.spawn((
+        TransformBundle::from_transform(Transform::from_translation(
+            (position + spawn.offset * mirror).extend(0.),
+        )),
+        RigidBody::KinematicVelocityBased,
+        Velocity::linear(spawn.velocity * mirror),
+        Collider::cuboid(spawn.half_extents.x, spawn.half_extents.y),
+        Sensor,
+        Projectile {
+            owner,
+            gravity: spawn.gravity,
+            lifetime: Timer::from_seconds(spawn.lifetime, TimerMode::Once),
+            hits_left: spawn.hits,
+        },
+        CharacterAttack {
+            damage: spawn.damage * stale_multiplier,
+            knockback: spawn.knockback * mirror * stale_multiplier,
+            attack_kind: Some(attack_kind),
+            ..default()
+        },
+        ActiveEvents::COLLISION_EVENTS,
+        // Kinematic bodies don't collide with fixed ones by default
+        ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC,
+        ColliderKind::Projectile.collision_groups(),
+    ));
+}
+
+fn projectile_movement(
+    mut projectile_query: Query<(Entity, &mut Projectile, &mut Velocity)>,
+    stage: Res<StageFile>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut projectile, mut vel) in projectile_query.iter_mut() {
+        if projectile.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        // Kinematic bodies don't have gravity
+        vel.linvel.y -= projectile.gravity
+            * stage.physics.gravity_multiplier
+            * stage.physics.pixels_per_unit
+            * time.delta_seconds();
+    }
+}
+
+/// Projectiles hitting the stage, reflectors and absorbers.
+/// Projectiles hitting characters are handled by `attack_system`
+fn projectile_collisions(
+    mut projectile_query: Query<(&mut Projectile, &mut CharacterAttack, &mut Velocity)>,
+    hitbox_query: Query<(&CharacterAttack, &Parent), Without<Projectile>>,
+    stage_query: Query<(), With<Stage>>,
+    mut character_query: Query<&mut Character>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut commands: Commands,
+) {
+    for collision in collision_events.iter() {
+        let CollisionEvent::Started(col1, col2, _) = collision else {
+            continue;
+        };
+
+        let (projectile_entity, other) = if projectile_query.contains(*col1) {
+            (*col1, *col2)
+        } else if projectile_query.contains(*col2) {
+            (*col2, *col1)
+        } else {
+            continue;
+        };
+        let (mut projectile, mut attack, mut vel) =
+            projectile_query.get_mut(projectile_entity).unwrap();
+
+        if stage_query.contains(other) {
+            commands.entity(projectile_entity).despawn_recursive();
+            continue;
+        }
+
+        let Ok((hitbox, hitbox_owner)) = hitbox_query.get(other) else {
+            continue;
+        };
+        let hitbox_owner = hitbox_owner.get();
+
+        if hitbox_owner == projectile.owner {
+            continue;
+        }
+
+        match hitbox.kind {
+            HitboxKind::Reflect => {
+                vel.linvel *= -REFLECT_SPEED_MULTIPLIER;
+                attack.knockback.x *= -1.;
+                attack.damage *= REFLECT_DAMAGE_MULTIPLIER;
+                attack.has_attacked.clear();
+                // It isn't part of the thrower's move anymore
+                attack.attack_kind = None;
+                projectile.owner = hitbox_owner;
+            }
+            HitboxKind::Absorb => {
+                if let Ok(mut absorber) = character_query.get_mut(hitbox_owner) {
+                    absorber.add_damage(-attack.damage);
+                }
+                commands.entity(projectile_entity).despawn_recursive();
+            }
+            _ => {}
+        }
+    }
+}
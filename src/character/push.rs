@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::{grab::Grabbed, ledge::LedgeHang, CharacterMovement};
+use crate::{
+    ruleset::Ruleset,
+    stage::{Stage, StageFile},
+    GameStates,
+};
+
+pub struct PushPlugin;
+
+impl Plugin for PushPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            remove_push_velocity
+                .after(super::character_touching_stage_check)
+                .before(super::character_movement),
+        )
+        .add_system(
+            push_system
+                .after(super::character_movement)
+                .after(super::abilities::movement_abilities)
+                .in_set(OnUpdate(GameStates::Playing)),
+        );
+    }
+}
+
+/// The part of the character's horizontal velocity that comes from being pushed by others.
+/// Like `Launch`, it's kept apart so the character's own movement works as usual
+#[derive(Component, Debug, Clone, Default)]
+pub struct Pushed {
+    applied: f32,
+}
+
+/// Takes last frame's push out, so `character_movement` only sees the character's own
+fn remove_push_velocity(mut character_query: Query<(&mut Velocity, &mut Pushed)>) {
+    for (mut vel, mut pushed) in character_query.iter_mut() {
+        vel.linvel.x -= pushed.applied;
+        pushed.applied = 0.;
+    }
+}
+
+/// Characters standing inside each other slowly get pushed apart.
+/// Their colliders don't touch, so it's done with their velocity, and hits work as usual
+fn push_system(
+    mut character_query: Query<
+        (
+            &Transform,
+            &Collider,
+            &CharacterMovement,
+            &mut Velocity,
+            &mut Pushed,
+        ),
+        (Without<LedgeHang>, Without<Grabbed>),
+    >,
+    platform_query: Query<(&Transform, &Collider), With<Stage>>,
+    ruleset: Res<Ruleset>,
+    stage: Res<StageFile>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    if ruleset.push_strength <= 0. || delta <= 0. {
+        return;
+    }
+
+    let mut combinations = character_query.iter_combinations_mut();
+    while let Some(
+        [(tf_a, collider_a, movement_a, _, mut pushed_a), (tf_b, collider_b, movement_b, _, mut pushed_b)],
+    ) = combinations.fetch_next()
+    {
+        // Only grounded characters push each other
+        if !movement_a.is_on_stage() || !movement_b.is_on_stage() {
+            continue;
+        }
+
+        let distance = (tf_b.translation - tf_a.translation).truncate();
+        let overlap = super::ledge::collider_half_extents(collider_a)
+            + super::ledge::collider_half_extents(collider_b)
+            - distance.abs();
+        if overlap.x <= 0. || overlap.y <= 0. {
+            continue;
+        }
+
+        // Both move the same amount, never further than it takes to separate them
+        let speed =
+            (ruleset.push_strength * stage.physics.pixels_per_unit).min(overlap.x / 2. / delta);
+        let direction = if distance.x >= 0. { 1. } else { -1. };
+        pushed_a.applied -= speed * direction;
+        pushed_b.applied += speed * direction;
+    }
+
+    for (tf, _, movement, mut vel, mut pushed) in character_query.iter_mut() {
+        // Nobody gets pushed off the platform they're standing on
+        if let Some((platform_tf, platform_collider)) = movement
+            .standing_on
+            .and_then(|platform| platform_query.get(platform).ok())
+        {
+            let platform = platform_collider.raw.compute_local_aabb();
+            let x = tf.translation.x - platform_tf.translation.x;
+            let max_left = ((platform.mins.x - x) / delta).min(0.);
+            let max_right = ((platform.maxs.x - x) / delta).max(0.);
+            pushed.applied = pushed.applied.clamp(max_left, max_right);
+        }
+
+        vel.linvel.x += pushed.applied;
+    }
+}
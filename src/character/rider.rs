@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::CharacterMovement;
+use crate::stage::{move_platforms, MovingPlatform};
+
+pub struct RiderPlugin;
+
+impl Plugin for RiderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            remove_platform_velocity
+                .after(super::character_touching_stage_check)
+                .before(super::character_movement),
+        )
+        .add_system(
+            apply_platform_velocity
+                .after(super::character_movement)
+                .after(super::abilities::movement_abilities)
+                .after(move_platforms),
+        );
+    }
+}
+
+/// The part of the character's velocity that comes from the moving platform it's standing on.
+/// Like `Launch`, it's kept apart so the character's own movement works as usual
+#[derive(Component, Debug, Clone, Default)]
+pub struct PlatformRider {
+    applied: Vec2,
+}
+
+/// Takes last frame's platform velocity out, so `character_movement` only sees the character's own.
+/// Characters that left the platform, like by jumping off, keep it as momentum
+fn remove_platform_velocity(
+    mut character_query: Query<(&mut Velocity, &CharacterMovement, &mut PlatformRider)>,
+) {
+    for (mut vel, movement, mut rider) in character_query.iter_mut() {
+        if movement.standing_on.is_some() {
+            vel.linvel -= rider.applied;
+        }
+        rider.applied = Vec2::ZERO;
+    }
+}
+
+/// Characters standing on a moving platform move along with it
+fn apply_platform_velocity(
+    mut character_query: Query<(&mut Velocity, &CharacterMovement, &mut PlatformRider)>,
+    platform_query: Query<&MovingPlatform>,
+) {
+    for (mut vel, movement, mut rider) in character_query.iter_mut() {
+        let Some(platform) = movement
+            .standing_on
+            .and_then(|entity| platform_query.get(entity).ok())
+        else {
+            continue;
+        };
+
+        vel.linvel += platform.velocity;
+        rider.applied = platform.velocity;
+    }
+}
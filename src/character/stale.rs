@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::ruleset::StaleMoveRules;
+
+use super::moves::AttackKind;
+
+/// The last moves of the character that connected, newest first.
+/// The more often a move is in it, the weaker it gets
+#[derive(Component, Debug, Clone, Default)]
+pub struct StaleMoves {
+    queue: VecDeque<AttackKind>,
+}
+
+impl StaleMoves {
+    /// What the damage and knockback of `kind` get multiplied by
+    pub fn multiplier(&self, kind: AttackKind, rules: &StaleMoveRules) -> f32 {
+        if !rules.enabled {
+            return 1.;
+        }
+
+        let mut is_in_queue = false;
+        let mut multiplier = 1.;
+        for (queued, factor) in self.queue.iter().zip(rules.queue_factors.iter()) {
+            if *queued == kind {
+                is_in_queue = true;
+                multiplier -= factor;
+            }
+        }
+
+        if is_in_queue {
+            multiplier
+        } else {
+            rules.fresh_bonus
+        }
+    }
+
+    /// Adds a move that just connected
+    pub fn push(&mut self, kind: AttackKind, rules: &StaleMoveRules) {
+        self.queue.push_front(kind);
+        self.queue.truncate(rules.queue_factors.len());
+    }
+
+    /// Each different move in the queue, with its current multiplier
+    pub fn staleness<'a>(
+        &'a self,
+        rules: &'a StaleMoveRules,
+    ) -> impl Iterator<Item = (AttackKind, f32)> + 'a {
+        let mut seen = Vec::new();
+        self.queue.iter().filter_map(move |kind| {
+            if seen.contains(kind) {
+                return None;
+            }
+            seen.push(*kind);
+            Some((*kind, self.multiplier(*kind, rules)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> StaleMoveRules {
+        StaleMoveRules {
+            enabled: true,
+            queue_factors: vec![0.1, 0.05],
+            fresh_bonus: 1.05,
+        }
+    }
+
+    #[test]
+    fn empty_queue_gets_the_fresh_bonus() {
+        let stale_moves = StaleMoves::default();
+        assert_eq!(
+            stale_moves.multiplier(AttackKind::ForwardAir, &rules()),
+            1.05
+        );
+    }
+
+    #[test]
+    fn every_spot_in_the_queue_weakens_the_move() {
+        let mut stale_moves = StaleMoves::default();
+        stale_moves.push(AttackKind::ForwardAir, &rules());
+        stale_moves.push(AttackKind::UpSpecial, &rules());
+
+        // Newest first, so the forward air is in the second spot
+        let multiplier = stale_moves.multiplier(AttackKind::ForwardAir, &rules());
+        assert!((multiplier - 0.95).abs() < 1e-6);
+
+        stale_moves.push(AttackKind::ForwardAir, &rules());
+        let multiplier = stale_moves.multiplier(AttackKind::ForwardAir, &rules());
+        assert!((multiplier - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn moves_fall_out_of_the_queue() {
+        let mut stale_moves = StaleMoves::default();
+        stale_moves.push(AttackKind::ForwardAir, &rules());
+        stale_moves.push(AttackKind::UpSpecial, &rules());
+        stale_moves.push(AttackKind::UpSpecial, &rules());
+
+        assert_eq!(
+            stale_moves.multiplier(AttackKind::ForwardAir, &rules()),
+            1.05
+        );
+    }
+
+    #[test]
+    fn no_queue_factors_means_no_queue() {
+        let mut stale_moves = StaleMoves::default();
+        let rules = StaleMoveRules {
+            queue_factors: Vec::new(),
+            ..rules()
+        };
+        stale_moves.push(AttackKind::ForwardAir, &rules);
+
+        assert_eq!(stale_moves.multiplier(AttackKind::ForwardAir, &rules), 1.05);
+        assert_eq!(stale_moves.staleness(&rules).count(), 0);
+    }
+
+    #[test]
+    fn disabled_rules_change_nothing() {
+        let mut stale_moves = StaleMoves::default();
+        let rules = StaleMoveRules {
+            enabled: false,
+            ..rules()
+        };
+        stale_moves.push(AttackKind::ForwardAir, &rules);
+
+        assert_eq!(stale_moves.multiplier(AttackKind::ForwardAir, &rules), 1.);
+        assert_eq!(stale_moves.multiplier(AttackKind::UpSpecial, &rules), 1.);
+    }
+}
@@ -0,0 +1,282 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ledge::collider_half_extents, Character, CharacterAttackController, CharacterMovement, Hitstun,
+};
+use crate::ruleset::{Handicap, Ruleset};
+
+/// How much longer stun and sleep last for each percent of damage
+const STATUS_DURATION_PER_PERCENT: f32 = 0.01;
+/// How much each button press takes off a bury or a freeze, in seconds
+const STATUS_MASH: f32 = 0.1;
+/// How much bigger than the character the ice block of a freeze is, on each side
+const ICE_BLOCK_PADDING: f32 = 10.;
+/// In front of the character, so it looks encased
+const ICE_BLOCK_Z: f32 = 1.;
+
+pub struct StatusPlugin;
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyStatus>()
+            .add_event::<StatusExpired>()
+            .add_system(apply_statuses.before(status_system))
+            .add_system(status_system.before(super::character_movement))
+            .add_system(status_tint.after(status_system))
+            .add_system(ice_blocks.after(status_system));
+    }
+}
+
+/// What a hitbox does to the characters it hits, on top of the damage and knockback
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum StatusEffect {
+    /// Stuck in the stage until mashed out, the time is over or it's hit.
+    /// Characters in the air can't be buried
+    Bury { duration: f32 },
+    /// Can't act, for longer the more damage the character has
+    Stun { duration: f32 },
+    /// Like stun, but hits wake the character up
+    Sleep { duration: f32 },
+    /// Encased in a block of ice, until mashed out or the time is over.
+    /// The block is launched by the hit and slides along the stage, it can't be steered
+    Freeze { duration: f32 },
+    /// Deals `damage` every `interval` seconds, `ticks` times
+    DamageOverTime {
+        damage: f32,
+        interval: f32,
+        ticks: usize,
+    },
+}
+
+/// Sent by hits with a status effect
+pub struct ApplyStatus {
+    pub entity: Entity,
+    pub effect: StatusEffect,
+}
+
+/// Sent when a status effect is over, whatever the reason
+pub struct StatusExpired {
+    pub entity: Entity,
+    pub effect: StatusEffect,
+}
+
+/// The status effects the character has
+#[derive(Component, Debug, Clone, Default)]
+pub struct StatusEffects(Vec<ActiveStatus>);
+
+/// The ice a frozen character is encased in, a child of the character
+#[derive(Component)]
+struct IceBlock;
+
+#[derive(Debug, Clone)]
+struct ActiveStatus {
+    effect: StatusEffect,
+    timer: Timer,
+    /// Only used by damage over time
+    ticks_left: usize,
+}
+
+impl StatusEffect {
+    /// The color characters with the status are tinted with
+    pub fn tint(&self) -> Color {
+        match self {
+            StatusEffect::Bury { .. } => Color::rgb(0.7, 0.55, 0.4),
+            StatusEffect::Stun { .. } => Color::rgb(1., 1., 0.5),
+            StatusEffect::Sleep { .. } => Color::rgb(0.6, 0.6, 1.),
+            StatusEffect::Freeze { .. } => Color::rgb(0.6, 0.9, 1.),
+            StatusEffect::DamageOverTime { .. } => Color::rgb(1., 0.5, 0.5),
+        }
+    }
+
+    /// Whether the character can't act while it has the status
+    fn disables(&self) -> bool {
+        !matches!(self, StatusEffect::DamageOverTime { .. })
+    }
+
+    /// Whether the character can't move from where it is while it has the status
+    fn holds_in_place(&self) -> bool {
+        matches!(
+            self,
+            StatusEffect::Bury { .. } | StatusEffect::Stun { .. } | StatusEffect::Sleep { .. }
+        )
+    }
+}
+
+impl StatusEffects {
+    pub fn is_disabled(&self) -> bool {
+        self.0.iter().any(|status| status.effect.disables())
+    }
+
+    pub fn holds_in_place(&self) -> bool {
+        self.0.iter().any(|status| status.effect.holds_in_place())
+    }
+
+    /// Frozen characters keep the velocity of their launch, even along the stage
+    pub fn is_frozen(&self) -> bool {
+        self.0
+            .iter()
+            .any(|status| matches!(status.effect, StatusEffect::Freeze { .. }))
+    }
+
+    /// Ends every status at once, like when the character is KO'd
+    pub fn expire_all(&mut self, entity: Entity, expired_events: &mut EventWriter<StatusExpired>) {
+        for status in self.0.drain(..) {
+            expired_events.send(StatusExpired {
+                entity,
+                effect: status.effect,
+            });
+        }
+    }
+}
+
+fn apply_statuses(
+    mut apply_events: EventReader<ApplyStatus>,
+    mut character_query: Query<(&Character, &CharacterMovement, &mut StatusEffects)>,
+) {
+    for event in apply_events.iter() {
+        let Ok((character, movement, mut statuses)) = character_query.get_mut(event.entity) else {
+            continue;
+        };
+
+        let (duration, mode) = match event.effect {
+            StatusEffect::Bury { .. } if !movement.is_on_stage() => continue,
+            StatusEffect::Bury { duration } | StatusEffect::Freeze { duration } => {
+                (duration, TimerMode::Once)
+            }
+            StatusEffect::Stun { duration } | StatusEffect::Sleep { duration } => (
+                duration * (1. + character.percentage * STATUS_DURATION_PER_PERCENT),
+                TimerMode::Once,
+            ),
+            StatusEffect::DamageOverTime { interval, .. } => (interval, TimerMode::Repeating),
+        };
+        let ticks_left = match event.effect {
+            StatusEffect::DamageOverTime { ticks, .. } => ticks,
+            _ => 0,
+        };
+
+        // A new status replaces the one of the same kind
+        statuses.0.retain(|status| {
+            std::mem::discriminant(&status.effect) != std::mem::discriminant(&event.effect)
+        });
+        statuses.0.push(ActiveStatus {
+            effect: event.effect,
+            timer: Timer::from_seconds(duration, mode),
+            ticks_left,
+        });
+    }
+}
+
+/// Ticks the status effects, and ends them when they're over
+pub fn status_system(
+    mut character_query: Query<(
+        Entity,
+        &mut Character,
+        &CharacterMovement,
+        &CharacterAttackController,
+        &mut StatusEffects,
+        &Handicap,
+        Option<Ref<Hitstun>>,
+    )>,
+    ruleset: Res<Ruleset>,
+    time: Res<Time>,
+    mut expired_events: EventWriter<StatusExpired>,
+) {
+    for (entity, mut character, movement, attack, mut statuses, handicap, hitstun) in
+        character_query.iter_mut()
+    {
+        let mashed =
+            movement.wants_to_jump || movement.wants_to_shield || attack.wants_to_attack.is_some();
+        let got_hit = hitstun.map_or(false, |hitstun| hitstun.is_added());
+
+        statuses.0.retain_mut(|status| {
+            // The hit that applied the status doesn't count
+            let hit_after_status = got_hit && status.timer.elapsed_secs() > 0.;
+
+            let is_over = match status.effect {
+                StatusEffect::DamageOverTime { damage, .. } => {
+                    if status.timer.tick(time.delta()).just_finished() {
+                        character.add_damage(ruleset.damage_taken(damage, handicap));
+                        status.ticks_left = status.ticks_left.saturating_sub(1);
+                    }
+                    status.ticks_left == 0
+                }
+                StatusEffect::Bury { .. } | StatusEffect::Freeze { .. } => {
+                    if mashed {
+                        status.timer.tick(Duration::from_secs_f32(STATUS_MASH));
+                    }
+                    let is_bury = matches!(status.effect, StatusEffect::Bury { .. });
+                    status.timer.tick(time.delta()).finished() || (is_bury && hit_after_status)
+                }
+                StatusEffect::Stun { .. } => status.timer.tick(time.delta()).finished(),
+                StatusEffect::Sleep { .. } => {
+                    status.timer.tick(time.delta()).finished() || hit_after_status
+                }
+            };
+
+            if is_over {
+                expired_events.send(StatusExpired {
+                    entity,
+                    effect: status.effect,
+                });
+            }
+            !is_over
+        });
+    }
+}
+
+/// The tint hook, characters take the color of their newest status
+fn status_tint(mut character_query: Query<(&StatusEffects, &mut Sprite), Changed<StatusEffects>>) {
+    for (statuses, mut sprite) in character_query.iter_mut() {
+        sprite.color = statuses
+            .0
+            .last()
+            .map_or(Color::WHITE, |status| status.effect.tint());
+    }
+}
+
+/// Encases frozen characters in a block of ice, and breaks it when they're not anymore
+fn ice_blocks(
+    character_query: Query<
+        (Entity, &StatusEffects, &Collider, Option<&Children>),
+        Changed<StatusEffects>,
+    >,
+    ice_query: Query<(), With<IceBlock>>,
+    mut commands: Commands,
+) {
+    for (entity, statuses, collider, children) in character_query.iter() {
+        let ice_block = children.and_then(|children| {
+            children
+                .iter()
+                .find(|child| ice_query.get(**child).is_ok())
+                .copied()
+        });
+
+        match (statuses.is_frozen(), ice_block) {
+            (true, None) => {
+                let size = (collider_half_extents(collider) + ICE_BLOCK_PADDING) * 2.;
+                commands.entity(entity).with_children(|children| {
+                    children.spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::rgba(0.7, 0.9, 1., 0.6),
+                                custom_size: Some(size),
+                                ..default()
+                            },
+                            transform: Transform::from_translation(Vec3::Z * ICE_BLOCK_Z),
+                            ..default()
+                        },
+                        IceBlock,
+                    ));
+                });
+            }
+            (false, Some(ice_block)) => {
+                commands.entity(ice_block).despawn_recursive();
+            }
+            _ => {}
+        }
+    }
+}
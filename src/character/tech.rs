@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::{
+    moves::AttackKind, CharacterAttackController, CharacterMovement, Hitstun, Intangibility,
+};
+use crate::stage::StageFile;
+
+/// How long after pressing shield hitting the stage is a tech
+const TECH_WINDOW: f32 = 0.33;
+/// How long after pressing shield pressing it again does nothing, so mashing doesn't tech
+const TECH_LOCKOUT: f32 = 0.67;
+/// How long the character is intangible after teching
+const TECH_INTANGIBILITY: f32 = 0.33;
+/// How far a tech roll moves the character
+const TECH_ROLL_DISTANCE: f32 = 200.;
+/// After this long lying down, the character gets up on its own
+const KNOCKDOWN_MAX_TIME: f32 = 2.;
+/// How long the character is intangible while getting up
+const GETUP_INTANGIBILITY: f32 = 0.3;
+/// How far a getup roll moves the character
+const GETUP_ROLL_DISTANCE: f32 = 200.;
+/// Hits weaker than this on a lying character force it up instead of launching it
+const JAB_LOCK_KNOCKBACK: f32 = 300.;
+/// How long a jab locked character can't act for after being forced up
+const JAB_LOCK_HITSTUN: f32 = 0.3;
+
+pub struct TechPlugin;
+
+impl Plugin for TechPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(tech.after(super::character_touching_stage_check))
+            .add_system(knockdown);
+    }
+}
+
+/// Lying on the ground after missing a tech
+#[derive(Component, Debug, Clone)]
+pub struct Knockdown {
+    timer: Timer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KnockdownGetup {
+    Neutral,
+    Roll,
+    Attack,
+}
+
+impl Knockdown {
+    fn new() -> Self {
+        Self {
+            timer: Timer::from_seconds(KNOCKDOWN_MAX_TIME, TimerMode::Once),
+        }
+    }
+
+    /// Whether a hit with `knockback` jab locks a lying character.
+    /// If it does, it gets `Hitstun` instead of being launched
+    pub fn jab_lock(knockback: Vec2) -> Option<Hitstun> {
+        (knockback.length() < JAB_LOCK_KNOCKBACK).then(|| Hitstun::new(JAB_LOCK_HITSTUN, false))
+    }
+}
+
+/// Tumbling characters hitting the stage. Pressing shield right before
+/// techs, missing it on the ground leaves the character lying down.
+/// Whether the stage was hit comes from `character_touching_stage_check`
+fn tech(
+    mut character_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut CharacterMovement,
+            Option<&mut Hitstun>,
+        ),
+        Without<Knockdown>,
+    >,
+    stage: Res<StageFile>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut tf, mut vel, mut movement, hitstun) in character_query.iter_mut() {
+        let wants_to_tech = movement.wants_to_tech;
+        movement.wants_to_tech = false;
+
+        let Some(mut hitstun) = hitstun else {
+            continue;
+        };
+
+        if let Some(lockout) = &mut hitstun.tech_lockout {
+            if lockout.tick(time.delta()).finished() {
+                hitstun.tech_lockout = None;
+            }
+        }
+        if let Some(window) = &mut hitstun.tech_window {
+            if window.tick(time.delta()).finished() {
+                hitstun.tech_window = None;
+            }
+        }
+
+        if wants_to_tech && hitstun.tech_lockout.is_none() {
+            hitstun.tech_window = Some(Timer::from_seconds(TECH_WINDOW, TimerMode::Once));
+            hitstun.tech_lockout = Some(Timer::from_seconds(TECH_LOCKOUT, TimerMode::Once));
+        }
+
+        let touching_stage = !movement.is_not_touching_stage() || movement.is_on_stage_ceiling();
+        if !touching_stage {
+            hitstun.airborne = true;
+            continue;
+        }
+
+        // Characters hit on the stage are touching it before they get launched
+        if !hitstun.airborne || !hitstun.tumble {
+            continue;
+        }
+
+        if hitstun.tech_window.is_some() {
+            // Tech roll
+            if movement.is_on_stage() && movement.x != 0. {
+                tf.translation.x += movement.x.signum() * TECH_ROLL_DISTANCE;
+            }
+            vel.linvel = Vec2::ZERO;
+            commands
+                .entity(entity)
+                .remove::<Hitstun>()
+                .insert(Intangibility(Timer::from_seconds(
+                    TECH_INTANGIBILITY,
+                    TimerMode::Once,
+                )));
+        } else if movement.is_on_stage() {
+            if let Some(bounce) = hitstun.bounce.take() {
+                // Meteor smashes bounce off the stage once
+                vel.linvel = bounce * stage.physics.pixels_per_unit;
+                hitstun.airborne = false;
+            } else {
+                vel.linvel = Vec2::ZERO;
+                commands
+                    .entity(entity)
+                    .remove::<Hitstun>()
+                    .insert(Knockdown::new());
+            }
+        }
+        // Missing a wall or ceiling tech just bounces off it
+    }
+}
+
+/// Getting up after being knocked down
+fn knockdown(
+    mut character_query: Query<(
+        Entity,
+        &mut Transform,
+        &mut Velocity,
+        &mut CharacterMovement,
+        &mut CharacterAttackController,
+        &mut Knockdown,
+    )>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut tf, mut vel, mut movement, mut attack, mut knockdown) in
+        character_query.iter_mut()
+    {
+        let getup = if knockdown.timer.tick(time.delta()).finished() {
+            Some(KnockdownGetup::Neutral)
+        } else if attack.wants_to_attack.is_some() {
+            Some(KnockdownGetup::Attack)
+        } else if movement.wants_to_shield || movement.x != 0. {
+            Some(KnockdownGetup::Roll)
+        } else if movement.wants_to_jump {
+            Some(KnockdownGetup::Neutral)
+        } else {
+            None
+        };
+
+        // Reset the variables, since `character_movement` doesn't run while lying down
+        movement.wants_to_jump = false;
+        movement.wants_to_fastfall = false;
+        movement.wants_to_drop_through = false;
+        movement.wants_to_shield = false;
+        movement.wants_to_crouch = false;
+        attack.wants_to_attack = None;
+
+        vel.linvel.x = 0.;
+
+        let Some(getup) = getup else {
+            continue;
+        };
+
+        match getup {
+            KnockdownGetup::Roll => {
+                let direction = if movement.x != 0. {
+                    movement.x.signum()
+                } else {
+                    movement.facing
+                };
+                tf.translation.x += direction * GETUP_ROLL_DISTANCE;
+            }
+            KnockdownGetup::Attack => {
+                attack.wants_to_attack = Some(AttackKind::GetupAttack);
+            }
+            KnockdownGetup::Neutral => {}
+        }
+
+        commands
+            .entity(entity)
+            .remove::<Knockdown>()
+            .insert(Intangibility(Timer::from_seconds(
+                GETUP_INTANGIBILITY,
+                TimerMode::Once,
+            )));
+    }
+}
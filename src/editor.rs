@@ -1,8 +1,22 @@
 use bevy::prelude::*;
+use bevy_egui::EguiContexts;
 use bevy_rapier2d::prelude::RapierConfiguration;
 use leafwing_input_manager::prelude::*;
 
-use crate::{character::Character, GameStates};
+use crate::{
+    character::Character,
+    mask::MaskOptions,
+    stage::{PlatformDefinition, StageFile},
+    GameStates,
+};
+
+/// How close the cursor has to be to grab something in the stage editor
+const HANDLE_SIZE: f32 = 40.;
+/// Platforms can't be resized smaller than this
+const MIN_PLATFORM_HALF_EXTENTS: f32 = 5.;
+const NEW_PLATFORM_HALF_EXTENTS: Vec2 = Vec2::new(125., 10.);
+/// Over the stage, so the markers can be seen
+const MARKER_Z: f32 = 10.;
 
 pub struct EditorPlugin;
 
@@ -11,6 +25,8 @@ impl Plugin for EditorPlugin {
         app.add_startup_system(editor_setup)
             .add_plugin(InputManagerPlugin::<EditorActions>::default())
             .add_systems((editor_toggle, editor_main, pick_character))
+            .add_system(stage_editor.in_set(OnUpdate(GameStates::Editor)))
+            .add_system(stage_markers)
             .insert_resource(EditorOptions::default());
     }
 }
@@ -20,11 +36,58 @@ enum EditorActions {
     Toggle,
 }
 
-#[derive(Default, Resource)]
+#[derive(Default, Resource, Clone, PartialEq)]
 pub struct EditorOptions {
     pub editing_character: Option<Entity>,
+
+    pub editing_stage: bool,
+    pub selected_platform: Option<usize>,
+    /// Positions and sizes snap to multiples of it, 0 turns snapping off
+    pub grid: f32,
+    /// The Tiled or LDtk map to make the stage from
+    pub import_path: String,
+    /// The sprite to make platforms from
+    pub mask: MaskOptions,
+    /// What happened with the last import or save, like an error
+    pub stage_message: Option<String>,
+    stage_drag: Option<StageDrag>,
+}
+
+impl EditorOptions {
+    /// For when the stage's platforms are replaced, the indices don't point to the same ones
+    pub fn clear_stage_selection(&mut self) {
+        self.selected_platform = None;
+        self.stage_drag = None;
+    }
 }
 
+/// What's being dragged in the stage editor
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StageDrag {
+    /// Keeps the platform where the cursor grabbed it
+    Platform {
+        index: usize,
+        grab_offset: Vec2,
+    },
+    /// Resizes the platform from its top right corner
+    PlatformCorner(usize),
+    SpawnPoint(usize),
+    RespawnPoint(usize),
+    BlastZoneEdge(Edge),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+/// Shows the spawn points, blast zone and selected platform in the stage editor
+#[derive(Component)]
+struct StageMarker;
+
 fn editor_setup(mut commands: Commands) {
     commands.spawn(InputManagerBundle::<EditorActions> {
         input_map: InputMap::new([(KeyCode::Escape, EditorActions::Toggle)])
@@ -54,6 +117,7 @@ fn editor_toggle(
                 rapier_configuration.physics_pipeline_active = true;
                 next_state.set(GameStates::Playing);
             }
+            GameStates::StageSelect => {}
         }
     }
 }
@@ -67,14 +131,14 @@ fn pick_character(
     characters: Query<(Entity, &GlobalTransform), With<Character>>,
     mut options: ResMut<EditorOptions>,
 ) {
+    if options.editing_stage {
+        return;
+    }
+
     let window = window.single();
     let (camera, gtansf) = camera.single();
 
-    if let Some(position) = window
-        .cursor_position()
-        .and_then(|cursor| camera.viewport_to_world(gtansf, cursor))
-        .map(|ray| ray.origin.truncate())
-    {
+    if let Some(position) = cursor_world_position(window, camera, gtansf) {
         if kb.just_pressed(MouseButton::Left) {
             for (entity, gtransf) in characters.iter() {
                 // TODO
@@ -85,3 +149,265 @@ fn pick_character(
         }
     }
 }
+
+/// Where the cursor is in the world, if it's inside the window
+fn cursor_world_position(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate())
+}
+
+fn snap(position: Vec2, grid: f32) -> Vec2 {
+    if grid > 0. {
+        (position / grid).round() * grid
+    } else {
+        position
+    }
+}
+
+/// Dragging things around in the stage, the rest of the editing is in the editor UI.
+/// Right click places a platform and Delete removes the selected one
+fn stage_editor(
+    window: Query<&Window>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut options: ResMut<EditorOptions>,
+    mut stage: ResMut<StageFile>,
+) {
+    if !options.editing_stage {
+        return;
+    }
+
+    if mouse.just_released(MouseButton::Left) {
+        options.stage_drag = None;
+    }
+
+    if keyboard.just_pressed(KeyCode::Delete) {
+        if let Some(index) = options.selected_platform {
+            if index < stage.platforms.len() {
+                stage.platforms.remove(index);
+            }
+            options.clear_stage_selection();
+        }
+    }
+
+    let window = window.single();
+    let (camera, gtansf) = camera.single();
+    let Some(cursor) = cursor_world_position(window, camera, gtansf) else {
+        return;
+    };
+    let snapped = snap(cursor, options.grid);
+
+    // Clicks on the editor panel aren't for the stage
+    let over_ui = contexts.ctx_mut().wants_pointer_input();
+
+    if mouse.just_pressed(MouseButton::Right) && !over_ui {
+        stage.platforms.push(PlatformDefinition {
+            position: snapped,
+            half_extents: NEW_PLATFORM_HALF_EXTENTS,
+            shape: default(),
+            soft: false,
+            ledges: false,
+            path: None,
+        });
+        options.selected_platform = Some(stage.platforms.len() - 1);
+    }
+
+    if mouse.just_pressed(MouseButton::Left) && !over_ui {
+        options.stage_drag = stage_handle_at(&stage, cursor);
+        options.selected_platform = match options.stage_drag {
+            Some(StageDrag::Platform { index, .. } | StageDrag::PlatformCorner(index)) => {
+                Some(index)
+            }
+            _ => None,
+        };
+    }
+
+    let Some(drag) = options.stage_drag else {
+        return;
+    };
+
+    // Only mark the stage changed when something moved, so it isn't respawned every frame.
+    // Nothing is there when what was dragged is gone, like a point removed from the UI
+    let file = stage.bypass_change_detection();
+    let moved = match drag {
+        StageDrag::Platform { index, grab_offset } => {
+            let position = snap(cursor - grab_offset, options.grid);
+            file.platforms
+                .get_mut(index)
+                .map(|platform| replace(&mut platform.position, position))
+        }
+        StageDrag::PlatformCorner(index) => file.platforms.get_mut(index).map(|platform| {
+            let half_extents = (snapped - platform.position)
+                .abs()
+                .max(Vec2::splat(MIN_PLATFORM_HALF_EXTENTS));
+            let resized = platform.half_extents != half_extents;
+            if resized {
+                platform.resize(half_extents);
+            }
+            resized
+        }),
+        StageDrag::SpawnPoint(index) => file
+            .spawn_points
+            .get_mut(index)
+            .map(|point| replace(point, snapped)),
+        StageDrag::RespawnPoint(index) => file
+            .respawn_points
+            .get_mut(index)
+            .map(|point| replace(point, snapped)),
+        StageDrag::BlastZoneEdge(edge) => {
+            let zone = &mut file.blast_zone;
+            Some(match edge {
+                Edge::Left => replace(&mut zone.min.x, snapped.x.min(zone.max.x)),
+                Edge::Right => replace(&mut zone.max.x, snapped.x.max(zone.min.x)),
+                Edge::Bottom => replace(&mut zone.min.y, snapped.y.min(zone.max.y)),
+                Edge::Top => replace(&mut zone.max.y, snapped.y.max(zone.min.y)),
+            })
+        }
+    };
+
+    match moved {
+        Some(true) => stage.set_changed(),
+        Some(false) => {}
+        None => options.clear_stage_selection(),
+    }
+}
+
+/// Sets `value` to `new`, and tells whether it was different
+fn replace<T: PartialEq>(value: &mut T, new: T) -> bool {
+    let changed = *value != new;
+    if changed {
+        *value = new;
+    }
+    changed
+}
+
+/// What's under the cursor in the stage editor. Points go first,
+/// then the newest platforms and last the blast zone edges
+fn stage_handle_at(stage: &StageFile, cursor: Vec2) -> Option<StageDrag> {
+    let near = |point: &Vec2| point.distance(cursor) < HANDLE_SIZE;
+
+    if let Some(index) = stage.spawn_points.iter().position(near) {
+        return Some(StageDrag::SpawnPoint(index));
+    }
+    if let Some(index) = stage.respawn_points.iter().position(near) {
+        return Some(StageDrag::RespawnPoint(index));
+    }
+
+    for (index, platform) in stage.platforms.iter().enumerate().rev() {
+        if near(&(platform.position + platform.half_extents)) {
+            return Some(StageDrag::PlatformCorner(index));
+        }
+        if (cursor - platform.position)
+            .abs()
+            .cmple(platform.half_extents)
+            .all()
+        {
+            return Some(StageDrag::Platform {
+                index,
+                grab_offset: cursor - platform.position,
+            });
+        }
+    }
+
+    let zone = stage.blast_zone;
+    [
+        (Edge::Left, (cursor.x - zone.min.x).abs()),
+        (Edge::Right, (cursor.x - zone.max.x).abs()),
+        (Edge::Bottom, (cursor.y - zone.min.y).abs()),
+        (Edge::Top, (cursor.y - zone.max.y).abs()),
+    ]
+    .into_iter()
+    .filter(|(_, distance)| *distance < HANDLE_SIZE)
+    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    .map(|(edge, _)| StageDrag::BlastZoneEdge(edge))
+}
+
+/// Spawns the markers again whenever the stage or the selection changes
+fn stage_markers(
+    mut commands: Commands,
+    marker_query: Query<Entity, With<StageMarker>>,
+    options: Res<EditorOptions>,
+    stage: Res<StageFile>,
+    state: Res<State<GameStates>>,
+) {
+    if !options.is_changed() && !stage.is_changed() && !state.is_changed() {
+        return;
+    }
+
+    for entity in marker_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    if state.0 != GameStates::Editor || !options.editing_stage {
+        return;
+    }
+
+    let mut spawn_marker = |position: Vec2, size: Vec2, color: Color| {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                transform: Transform::from_translation(position.extend(MARKER_Z)),
+                ..default()
+            },
+            StageMarker,
+        ));
+    };
+
+    for point in &stage.spawn_points {
+        spawn_marker(*point, Vec2::splat(HANDLE_SIZE), Color::GREEN);
+    }
+    for point in &stage.respawn_points {
+        spawn_marker(*point, Vec2::splat(HANDLE_SIZE), Color::BLUE);
+    }
+
+    let zone = stage.blast_zone;
+    let (center, size) = (zone.center(), zone.size());
+    for (position, size) in [
+        (
+            Vec2::new(zone.min.x, center.y),
+            Vec2::new(HANDLE_SIZE, size.y),
+        ),
+        (
+            Vec2::new(zone.max.x, center.y),
+            Vec2::new(HANDLE_SIZE, size.y),
+        ),
+        (
+            Vec2::new(center.x, zone.min.y),
+            Vec2::new(size.x, HANDLE_SIZE),
+        ),
+        (
+            Vec2::new(center.x, zone.max.y),
+            Vec2::new(size.x, HANDLE_SIZE),
+        ),
+    ] {
+        spawn_marker(position, size, Color::RED.with_a(0.5));
+    }
+
+    if let Some(platform) = options
+        .selected_platform
+        .and_then(|index| stage.platforms.get(index))
+    {
+        spawn_marker(
+            platform.position,
+            platform.half_extents * 2.,
+            Color::YELLOW.with_a(0.5),
+        );
+        spawn_marker(
+            platform.position + platform.half_extents,
+            Vec2::splat(HANDLE_SIZE / 2.),
+            Color::ORANGE,
+        );
+    }
+}
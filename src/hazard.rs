@@ -0,0 +1,366 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    character::{Blown, Character, HazardHitbox, StatusEffect},
+    ruleset::Ruleset,
+    stage::{spawn_platform, PlatformDefinition, StageFile},
+    GameStates,
+};
+
+/// How often lava burns characters standing in it, in seconds
+const LAVA_REHIT_RATE: f32 = 0.5;
+/// Under the characters, but over the background
+const HAZARD_Z: f32 = -1.;
+
+pub struct HazardPlugin;
+
+impl Plugin for HazardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(respawn_hazards).add_systems(
+            (
+                hazard_cycles,
+                wind,
+                emitters,
+                emitted_lifetime,
+                transform_stage,
+            )
+                .in_set(OnUpdate(GameStates::Playing)),
+        );
+    }
+}
+
+/// A hazard of a stage, only there if the ruleset has hazards on
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HazardDefinition {
+    pub position: Vec2,
+    pub half_extents: Vec2,
+    pub kind: HazardKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum HazardKind {
+    /// Hits whoever is inside
+    DamageZone {
+        hit: HazardHit,
+        #[serde(default)]
+        cycle: Option<HazardCycle>,
+    },
+    /// Burns whoever touches it and pops them up
+    Lava { damage: f32, knockback: f32 },
+    /// Pushes whoever is inside, at `velocity`
+    Wind {
+        velocity: Vec2,
+        #[serde(default)]
+        cycle: Option<HazardCycle>,
+    },
+    /// Throws hitboxes of its size from its position
+    Emitter {
+        hit: HazardHit,
+        /// How often it throws one, in seconds
+        interval: f32,
+        velocity: Vec2,
+        /// How long each one lasts, in seconds
+        lifetime: f32,
+    },
+}
+
+/// What a hazard does to the characters it hits
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HazardHit {
+    pub damage: f32,
+    pub knockback: Vec2,
+    /// How often it can hit the same character, in seconds. Without it, only once
+    #[serde(default)]
+    pub rehit_rate: Option<f32>,
+    #[serde(default)]
+    pub status: Option<StatusEffect>,
+}
+
+/// Turns a hazard on and off. It starts on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct HazardCycle {
+    /// How long it stays on, in seconds
+    pub on: f32,
+    /// How long it stays off, in seconds
+    pub off: f32,
+}
+
+/// The stage changes between layouts, like a stage that transforms
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StageTransformation {
+    /// How long each layout stays, in seconds
+    pub interval: f32,
+    /// Platforms added on top of the stage's own, one layout after another
+    pub layouts: Vec<Vec<PlatformDefinition>>,
+}
+
+/// Anything spawned by the hazards, gone with them
+#[derive(Component)]
+struct HazardElement;
+
+#[derive(Component, Debug, Clone)]
+struct Cycling {
+    cycle: HazardCycle,
+    time: f32,
+    on: bool,
+}
+
+#[derive(Component, Debug, Clone)]
+struct Wind {
+    velocity: Vec2,
+    half_extents: Vec2,
+}
+
+#[derive(Component, Debug, Clone)]
+struct Emitter {
+    hit: HazardHit,
+    timer: Timer,
+    velocity: Vec2,
+    half_extents: Vec2,
+    lifetime: f32,
+}
+
+/// Hitboxes thrown by emitters, gone when the timer is finished
+#[derive(Component, Debug, Clone)]
+struct EmittedLifetime(Timer);
+
+/// The layout the stage is in right now
+#[derive(Component, Debug, Clone)]
+struct Transforming {
+    transformation: StageTransformation,
+    timer: Timer,
+    layout: usize,
+}
+
+/// The platforms of the current layout
+#[derive(Component)]
+struct LayoutPlatform;
+
+impl HazardCycle {
+    fn is_on(&self, time: f32) -> bool {
+        time.rem_euclid(self.on + self.off) < self.on
+    }
+}
+
+/// Spawns the hazards again when the stage or the ruleset changes
+fn respawn_hazards(
+    mut commands: Commands,
+    hazard_query: Query<Entity, With<HazardElement>>,
+    stage: Res<StageFile>,
+    ruleset: Res<Ruleset>,
+) {
+    if !stage.is_changed() && !ruleset.is_changed() {
+        return;
+    }
+
+    for entity in hazard_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    if !ruleset.hazards {
+        return;
+    }
+
+    for hazard in &stage.hazards {
+        spawn_hazard(&mut commands, hazard);
+    }
+
+    if let Some(transformation) = &stage.transformation {
+        commands.spawn((
+            Transforming {
+                transformation: transformation.clone(),
+                timer: Timer::from_seconds(transformation.interval, TimerMode::Repeating),
+                layout: 0,
+            },
+            HazardElement,
+        ));
+        if let Some(layout) = transformation.layouts.first() {
+            spawn_layout(&mut commands, layout);
+        }
+    }
+}
+
+fn spawn_hazard(commands: &mut Commands, hazard: &HazardDefinition) {
+    let color = match hazard.kind {
+        HazardKind::Lava { .. } => Color::rgba(1., 0.4, 0., 0.7),
+        HazardKind::Wind { .. } => Color::rgba(0.8, 0.9, 1., 0.2),
+        HazardKind::DamageZone { .. } | HazardKind::Emitter { .. } => Color::rgba(1., 0., 0., 0.3),
+    };
+    let mut entity = commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(hazard.half_extents * 2.),
+                ..default()
+            },
+            transform: Transform::from_translation(hazard.position.extend(HAZARD_Z)),
+            ..default()
+        },
+        HazardElement,
+    ));
+
+    match &hazard.kind {
+        HazardKind::DamageZone { hit, cycle } => {
+            entity.insert((
+                Collider::cuboid(hazard.half_extents.x, hazard.half_extents.y),
+                HazardHitbox::bundle(hit.damage, hit.knockback, hit.rehit_rate, hit.status),
+            ));
+            if let Some(cycle) = cycle {
+                entity.insert(Cycling {
+                    cycle: *cycle,
+                    time: 0.,
+                    on: true,
+                });
+            }
+        }
+        HazardKind::Lava { damage, knockback } => {
+            entity.insert((
+                Collider::cuboid(hazard.half_extents.x, hazard.half_extents.y),
+                HazardHitbox::bundle(*damage, Vec2::Y * *knockback, Some(LAVA_REHIT_RATE), None),
+            ));
+        }
+        HazardKind::Wind { velocity, cycle } => {
+            entity.insert(Wind {
+                velocity: *velocity,
+                half_extents: hazard.half_extents,
+            });
+            if let Some(cycle) = cycle {
+                entity.insert(Cycling {
+                    cycle: *cycle,
+                    time: 0.,
+                    on: true,
+                });
+            }
+        }
+        HazardKind::Emitter {
+            hit,
+            interval,
+            velocity,
+            lifetime,
+        } => {
+            entity.insert(Emitter {
+                hit: hit.clone(),
+                timer: Timer::from_seconds(*interval, TimerMode::Repeating),
+                velocity: *velocity,
+                half_extents: hazard.half_extents,
+                lifetime: *lifetime,
+            });
+        }
+    }
+}
+
+fn spawn_layout(commands: &mut Commands, layout: &[PlatformDefinition]) {
+    for platform in layout {
+        let entity = spawn_platform(commands, platform);
+        commands
+            .entity(entity)
+            .insert((LayoutPlatform, HazardElement));
+    }
+}
+
+/// Turns cycling hazards on and off
+fn hazard_cycles(
+    mut hazard_query: Query<(Entity, &mut Cycling, &mut Visibility)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut cycling, mut visibility) in hazard_query.iter_mut() {
+        cycling.time += time.delta_seconds();
+
+        let on = cycling.cycle.is_on(cycling.time);
+        if on == cycling.on {
+            continue;
+        }
+        cycling.on = on;
+
+        if on {
+            *visibility = Visibility::Inherited;
+            commands.entity(entity).remove::<ColliderDisabled>();
+        } else {
+            *visibility = Visibility::Hidden;
+            commands.entity(entity).insert(ColliderDisabled);
+        }
+    }
+}
+
+/// Pushes the characters inside the wind along with it
+pub fn wind(
+    wind_query: Query<(&Transform, &Wind, Option<&Cycling>), Without<Character>>,
+    mut character_query: Query<(&Transform, &mut Blown), With<Character>>,
+) {
+    for (wind_tf, wind, cycling) in wind_query.iter() {
+        if cycling.map_or(false, |cycling| !cycling.on) {
+            continue;
+        }
+
+        for (tf, mut blown) in character_query.iter_mut() {
+            let distance = (tf.translation - wind_tf.translation).truncate().abs();
+            if distance.cmple(wind.half_extents).all() {
+                blown.velocity += wind.velocity;
+            }
+        }
+    }
+}
+
+fn emitters(
+    mut emitter_query: Query<(&Transform, &mut Emitter)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (tf, mut emitter) in emitter_query.iter_mut() {
+        if !emitter.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let hit = &emitter.hit;
+        commands.spawn((
+            TransformBundle::from_transform(*tf),
+            RigidBody::KinematicVelocityBased,
+            Velocity::linear(emitter.velocity),
+            Collider::cuboid(emitter.half_extents.x, emitter.half_extents.y),
+            HazardHitbox::bundle(hit.damage, hit.knockback, hit.rehit_rate, hit.status),
+            EmittedLifetime(Timer::from_seconds(emitter.lifetime, TimerMode::Once)),
+            HazardElement,
+        ));
+    }
+}
+
+fn emitted_lifetime(
+    mut emitted_query: Query<(Entity, &mut EmittedLifetime)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut lifetime) in emitted_query.iter_mut() {
+        if lifetime.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Swaps the layout of transforming stages
+fn transform_stage(
+    mut transforming_query: Query<&mut Transforming>,
+    layout_query: Query<Entity, With<LayoutPlatform>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for mut transforming in transforming_query.iter_mut() {
+        if !transforming.timer.tick(time.delta()).just_finished()
+            || transforming.transformation.layouts.is_empty()
+        {
+            continue;
+        }
+
+        for entity in layout_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        transforming.layout = (transforming.layout + 1) % transforming.transformation.layouts.len();
+        spawn_layout(
+            &mut commands,
+            &transforming.transformation.layouts[transforming.layout],
+        );
+    }
+}
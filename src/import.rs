@@ -0,0 +1,931 @@
+use std::{
+    fs::OpenOptions,
+    io::{BufWriter, ErrorKind},
+    path::Path,
+};
+
+use bevy::prelude::*;
+use image::{imageops, ImageOutputFormat, RgbaImage};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    stage::{BackgroundLayer, PlatformDefinition, StageFile},
+    ASSETS_DIRECTORY,
+};
+
+/// How far the blast zone is from the map when the map doesn't have one
+const DEFAULT_BLAST_ZONE_MARGIN: f32 = 1000.;
+/// Where the images of tile layers are saved, relative to `assets`
+const BAKED_TILES_DIR: &str = "stages";
+
+/// Flags in the top bits of Tiled tile ids
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// Only used by hexagonal maps, it's ignored
+const ROTATED_HEXAGONAL: u32 = 0x1000_0000;
+const TILE_FLAGS: u32 =
+    FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL;
+
+/// Makes a stage out of a Tiled (.tmx or .tmj) or LDtk (.ldtk) map.
+///
+/// Objects (or entities in LDtk) are read by class: `platform`, `soft_platform`,
+/// `spawn`, `respawn`, `blast_zone` and `camera_bounds`. Platforms with a `ledges`
+/// property set get ledges. Tiles of tile layers with a `collision` property set
+/// (IntGrid layers named `collision` in LDtk) become solid platforms.
+/// The map is centered on the origin and y points up.
+/// Each layer of tiles is drawn from its tileset into an image in `assets/stages`,
+/// which becomes a background layer of the stage.
+/// Background images keep their path in the map, so they may have to be moved into `assets`
+pub fn import_stage(path: &str) -> Result<StageFile, String> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    let name = Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let file =
+        std::fs::read_to_string(path).map_err(|error| format!("Couldn't read {path}: {error}"))?;
+    let invalid = |error: serde_json::Error| format!("{path} isn't a valid map: {error}");
+
+    let stage = match extension {
+        "tmj" | "json" => tiled_stage(serde_json::from_str(&file).map_err(invalid)?, dir, name)?,
+        "tmx" => tiled_stage(
+            parse_tmx(&file).map_err(|error| format!("{path}: {error}"))?,
+            dir,
+            name,
+        )?,
+        "ldtk" => ldtk_stage(serde_json::from_str(&file).map_err(invalid)?, dir, name)?,
+        _ => return Err(format!("Can't import stages from .{extension} files")),
+    };
+
+    Ok(StageFile {
+        name: if stage.name.is_empty() {
+            name.to_string()
+        } else {
+            stage.name
+        },
+        ..stage
+    })
+}
+
+/// The parts of a map a stage is made of, in world coordinates
+#[derive(Default)]
+struct MapObjects {
+    platforms: Vec<PlatformDefinition>,
+    spawn_points: Vec<Vec2>,
+    respawn_points: Vec<Vec2>,
+    blast_zone: Option<Rect>,
+    camera_bounds: Option<Rect>,
+}
+
+impl MapObjects {
+    fn add(&mut self, class: &str, rect: Rect, ledges: bool) {
+        let platform = |soft| PlatformDefinition {
+            position: rect.center(),
+            half_extents: rect.half_size(),
+            shape: default(),
+            soft,
+            ledges,
+            path: None,
+        };
+
+        match normalize(class).as_str() {
+            "platform" => self.platforms.push(platform(false)),
+            "softplatform" => self.platforms.push(platform(true)),
+            "spawn" => self.spawn_points.push(rect.center()),
+            "respawn" => self.respawn_points.push(rect.center()),
+            "blastzone" => self.blast_zone = Some(rect),
+            "camerabounds" => self.camera_bounds = Some(rect),
+            _ => {}
+        }
+    }
+
+    /// Solid platforms for the filled cells of a grid, `filled` is row by row from the top
+    fn add_grid(&mut self, filled: &[bool], columns: usize, cell_size: Vec2, map_size: Vec2) {
+        for rect in grid_rects(filled, columns) {
+            self.add(
+                "platform",
+                map_rect(rect.min * cell_size, rect.size() * cell_size, map_size),
+                false,
+            );
+        }
+    }
+
+    fn into_stage(
+        self,
+        name: String,
+        map_size: Vec2,
+        backgrounds: Vec<BackgroundLayer>,
+    ) -> StageFile {
+        let map_bounds = Rect::from_center_size(Vec2::ZERO, map_size);
+        let spawn_points = if self.spawn_points.is_empty() {
+            vec![Vec2::ZERO]
+        } else {
+            self.spawn_points
+        };
+
+        StageFile {
+            path: String::new(),
+            name,
+            platforms: self.platforms,
+            respawn_points: if self.respawn_points.is_empty() {
+                spawn_points.clone()
+            } else {
+                self.respawn_points
+            },
+            spawn_points,
+            blast_zone: self.blast_zone.unwrap_or(Rect::from_center_size(
+                Vec2::ZERO,
+                map_size + DEFAULT_BLAST_ZONE_MARGIN * 2.,
+            )),
+            camera_bounds: self.camera_bounds.unwrap_or(map_bounds),
+            backgrounds,
+            music: None,
+            thumbnail: None,
+            hazards: Vec::new(),
+            transformation: None,
+            physics: default(),
+        }
+    }
+}
+
+/// Rectangles covering the filled cells of a grid, in cells with y down.
+/// `filled` is row by row from the top. Runs of cells in a row become one rectangle,
+/// and so do the same runs in the rows below
+pub fn grid_rects(filled: &[bool], columns: usize) -> Vec<Rect> {
+    let rows = filled.len() / columns.max(1);
+    let mut rects = Vec::new();
+    // Runs of the last row, as (first column, last column, first row)
+    let mut open: Vec<(usize, usize, usize)> = Vec::new();
+
+    for row in 0..=rows {
+        let mut runs = Vec::new();
+        if row < rows {
+            let cells = &filled[row * columns..(row + 1) * columns];
+            let mut column = 0;
+            while column < columns {
+                if !cells[column] {
+                    column += 1;
+                    continue;
+                }
+                let start = column;
+                while column < columns && cells[column] {
+                    column += 1;
+                }
+                runs.push((start, column - 1));
+            }
+        }
+
+        let mut next_open = Vec::new();
+        for (start, end, first_row) in open.drain(..) {
+            match runs.iter().position(|run| *run == (start, end)) {
+                Some(index) => {
+                    runs.remove(index);
+                    next_open.push((start, end, first_row));
+                }
+                None => rects.push(Rect::new(
+                    start as f32,
+                    first_row as f32,
+                    (end + 1) as f32,
+                    row as f32,
+                )),
+            }
+        }
+        next_open.extend(runs.into_iter().map(|(start, end)| (start, end, row)));
+        open = next_open;
+    }
+
+    rects
+}
+
+/// Lowercase without underscores, so `SoftPlatform` and `soft_platform` are the same class
+fn normalize(class: &str) -> String {
+    class.to_lowercase().replace('_', "")
+}
+
+/// A rectangle of the map (top left corner, y down) in world coordinates
+fn map_rect(top_left: Vec2, size: Vec2, map_size: Vec2) -> Rect {
+    let center = top_left + size / 2.;
+    Rect::from_center_size(
+        Vec2::new(center.x - map_size.x / 2., map_size.y / 2. - center.y),
+        size,
+    )
+}
+
+/// How a tile is drawn. Diagonally flipped tiles have their x and y swapped before the other flips
+#[derive(Debug, Clone, Copy, Default)]
+struct TileFlip {
+    horizontal: bool,
+    vertical: bool,
+    diagonal: bool,
+}
+
+fn load_image(path: &Path) -> Result<RgbaImage, String> {
+    image::open(path)
+        .map(|image| image.to_rgba8())
+        .map_err(|error| format!("Couldn't open {}: {error}", path.display()))
+}
+
+/// Draws the `size` tile at `source` in the tileset onto the layer, `position` is its top left corner
+fn draw_tile(
+    layer: &mut RgbaImage,
+    tileset: &RgbaImage,
+    source: UVec2,
+    size: UVec2,
+    position: IVec2,
+    flip: TileFlip,
+) {
+    let mut tile = imageops::crop_imm(tileset, source.x, source.y, size.x, size.y).to_image();
+    if flip.diagonal {
+        tile = imageops::flip_horizontal(&imageops::rotate90(&tile));
+    }
+    if flip.horizontal {
+        imageops::flip_horizontal_in_place(&mut tile);
+    }
+    if flip.vertical {
+        imageops::flip_vertical_in_place(&mut tile);
+    }
+    imageops::overlay(layer, &tile, position.x as i64, position.y as i64);
+}
+
+/// Saves a layer drawn from tiles as `name`, as a background layer covering the map.
+/// Files already there are kept, the layer gets a number after its name instead
+fn save_tile_layer(
+    layer: &RgbaImage,
+    name: &str,
+    map_size: Vec2,
+) -> Result<BackgroundLayer, String> {
+    let mut copy = 0;
+    loop {
+        let image = match copy {
+            0 => format!("{BAKED_TILES_DIR}/{name}.png"),
+            _ => format!("{BAKED_TILES_DIR}/{name}_{copy}.png"),
+        };
+        let path = Path::new(ASSETS_DIRECTORY).join(&image);
+        let couldnt_save =
+            |error: &dyn std::fmt::Display| format!("Couldn't save {}: {error}", path.display());
+
+        let file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                copy += 1;
+                continue;
+            }
+            Err(error) => return Err(couldnt_save(&error)),
+        };
+        layer
+            .write_to(&mut BufWriter::new(file), ImageOutputFormat::Png)
+            .map_err(|error| couldnt_save(&error))?;
+
+        return Ok(BackgroundLayer {
+            image,
+            position: Some(Vec2::ZERO),
+            size: Some(map_size),
+            parallax: 0.,
+        });
+    }
+}
+
+// Tiled
+
+#[derive(Deserialize, Debug, Default)]
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    layers: Vec<TiledLayer>,
+    #[serde(default)]
+    tilesets: Vec<TiledTileset>,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct TiledLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    width: usize,
+    /// Tile ids, 0 is empty
+    #[serde(default)]
+    data: Vec<u32>,
+    #[serde(default)]
+    objects: Vec<TiledObject>,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+    /// Only image layers have it
+    #[serde(default)]
+    image: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct TiledTileset {
+    /// The id of its first tile in the map
+    #[serde(default)]
+    firstgid: u32,
+    /// Where the tileset is, relative to the map. Only tilesets in their own file have it
+    #[serde(default)]
+    source: String,
+    /// Relative to the file the tileset is in
+    #[serde(default)]
+    image: String,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    /// 0 if each tile is its own image
+    #[serde(default)]
+    columns: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    spacing: u32,
+}
+
+/// A tileset and its image, ready to draw tiles from
+struct LoadedTileset {
+    firstgid: u32,
+    tileset: TiledTileset,
+    /// Nothing for tilesets made of separate images, their tiles are left out
+    image: Option<RgbaImage>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct TiledObject {
+    #[serde(default, rename = "type")]
+    kind: String,
+    /// What `type` is called in newer versions of Tiled
+    #[serde(default)]
+    class: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TiledProperty {
+    name: String,
+    value: Value,
+}
+
+fn property<'a>(properties: &'a [TiledProperty], name: &str) -> Option<&'a Value> {
+    properties
+        .iter()
+        .find(|property| property.name == name)
+        .map(|property| &property.value)
+}
+
+fn tiled_stage(map: TiledMap, dir: &Path, file_name: &str) -> Result<StageFile, String> {
+    let tile_size = Vec2::new(map.tilewidth, map.tileheight);
+    let map_size = Vec2::new(map.width as f32, map.height as f32) * tile_size;
+    let tilesets = load_tilesets(&map.tilesets, dir)?;
+    let mut objects = MapObjects::default();
+    let mut backgrounds = Vec::new();
+
+    for (index, layer) in map.layers.iter().enumerate() {
+        match layer.kind.as_str() {
+            "tilelayer" => {
+                let collision = property(&layer.properties, "collision")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+                if collision {
+                    let filled: Vec<bool> = layer.data.iter().map(|tile| *tile != 0).collect();
+                    objects.add_grid(&filled, layer.width, tile_size, map_size);
+                }
+                if let Some(tiles) = draw_tiled_layer(layer, &tilesets, tile_size, map_size) {
+                    backgrounds.push(save_tile_layer(
+                        &tiles,
+                        &format!("{file_name}_{index}"),
+                        map_size,
+                    )?);
+                }
+            }
+            "objectgroup" => {
+                for object in &layer.objects {
+                    let class = if object.class.is_empty() {
+                        &object.kind
+                    } else {
+                        &object.class
+                    };
+                    let ledges = property(&object.properties, "ledges")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false);
+                    let rect = map_rect(
+                        Vec2::new(object.x, object.y),
+                        Vec2::new(object.width, object.height),
+                        map_size,
+                    );
+                    objects.add(class, rect, ledges);
+                }
+            }
+            "imagelayer" if !layer.image.is_empty() => {
+                backgrounds.push(image_layer(&layer.image));
+            }
+            _ => {}
+        }
+    }
+
+    let name = property(&map.properties, "name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    Ok(objects.into_stage(name, map_size, backgrounds))
+}
+
+/// Reads the tilesets in their own file and opens the images of all of them
+fn load_tilesets(tilesets: &[TiledTileset], dir: &Path) -> Result<Vec<LoadedTileset>, String> {
+    let mut loaded = Vec::new();
+
+    for tileset in tilesets {
+        let (tileset, tileset_dir) = if tileset.source.is_empty() {
+            (
+                TiledTileset {
+                    source: String::new(),
+                    image: tileset.image.clone(),
+                    ..*tileset
+                },
+                dir.to_path_buf(),
+            )
+        } else {
+            let path = dir.join(&tileset.source);
+            let file = std::fs::read_to_string(&path)
+                .map_err(|error| format!("Couldn't read {}: {error}", path.display()))?;
+            let external = if path
+                .extension()
+                .map_or(false, |extension| extension == "tsx")
+            {
+                let document =
+                    roxmltree::Document::parse(&file).map_err(|error| error.to_string())?;
+                tmx_tileset(document.root_element())
+            } else {
+                serde_json::from_str(&file).map_err(|error| error.to_string())
+            }
+            .map_err(|error| format!("{}: {error}", path.display()))?;
+
+            (
+                TiledTileset {
+                    firstgid: tileset.firstgid,
+                    ..external
+                },
+                path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            )
+        };
+
+        // Still there without an image, so its tiles aren't taken for another tileset's
+        let image = if tileset.columns == 0 {
+            warn!(
+                "Leaving out the tiles from id {}, tilesets made of separate images can't be imported",
+                tileset.firstgid
+            );
+            None
+        } else {
+            Some(load_image(&tileset_dir.join(&tileset.image))?)
+        };
+        loaded.push(LoadedTileset {
+            firstgid: tileset.firstgid,
+            image,
+            tileset,
+        });
+    }
+
+    loaded.sort_by_key(|tileset| tileset.firstgid);
+    Ok(loaded)
+}
+
+/// The tiles of a layer drawn into an image the size of the map, nothing if it has none
+fn draw_tiled_layer(
+    layer: &TiledLayer,
+    tilesets: &[LoadedTileset],
+    tile_size: Vec2,
+    map_size: Vec2,
+) -> Option<RgbaImage> {
+    if layer.width == 0 {
+        return None;
+    }
+
+    let mut image = RgbaImage::new(map_size.x as u32, map_size.y as u32);
+    let mut drawn = false;
+
+    for (index, tile) in layer.data.iter().enumerate() {
+        let id = tile & !TILE_FLAGS;
+        if id == 0 {
+            continue;
+        }
+        let Some(loaded) = tilesets.iter().rev().find(|tileset| tileset.firstgid <= id) else {
+            continue;
+        };
+        let Some(tileset_image) = &loaded.image else {
+            continue;
+        };
+
+        let tileset = &loaded.tileset;
+        let local_id = id - loaded.firstgid;
+        let source = UVec2::new(
+            local_id % tileset.columns * (tileset.tilewidth + tileset.spacing),
+            local_id / tileset.columns * (tileset.tileheight + tileset.spacing),
+        ) + tileset.margin;
+        let size = UVec2::new(tileset.tilewidth, tileset.tileheight);
+
+        // Tiles bigger than the map's go up and right from the bottom left corner of their cell
+        let cell = Vec2::new(
+            (index % layer.width) as f32,
+            (index / layer.width + 1) as f32,
+        );
+        let position = (cell * tile_size).as_ivec2() - IVec2::new(0, size.y as i32);
+
+        draw_tile(
+            &mut image,
+            tileset_image,
+            source,
+            size,
+            position,
+            TileFlip {
+                horizontal: tile & FLIPPED_HORIZONTALLY != 0,
+                vertical: tile & FLIPPED_VERTICALLY != 0,
+                diagonal: tile & FLIPPED_DIAGONALLY != 0,
+            },
+        );
+        drawn = true;
+    }
+
+    drawn.then_some(image)
+}
+
+/// An image covering the camera bounds, like a background image of a map
+fn image_layer(image: &str) -> BackgroundLayer {
+    BackgroundLayer {
+        image: image.to_string(),
+        position: None,
+        size: None,
+        parallax: 0.,
+    }
+}
+
+/// Reads a .tmx into the same thing a .tmj becomes. Only CSV tile data is supported
+fn parse_tmx(file: &str) -> Result<TiledMap, String> {
+    let document = roxmltree::Document::parse(file).map_err(|error| error.to_string())?;
+    let map = document.root_element();
+
+    let mut layers = Vec::new();
+    let mut tilesets = Vec::new();
+    for layer in map.children().filter(|node| node.is_element()) {
+        match layer.tag_name().name() {
+            "tileset" => tilesets.push(tmx_tileset(layer)?),
+            "layer" => layers.push(tmx_tile_layer(layer)?),
+            "objectgroup" => {
+                let objects = layer
+                    .children()
+                    .filter(|child| child.has_tag_name("object"))
+                    .map(|object| -> Result<TiledObject, String> {
+                        Ok(TiledObject {
+                            kind: object.attribute("type").unwrap_or_default().to_string(),
+                            class: object.attribute("class").unwrap_or_default().to_string(),
+                            x: tmx_number(object, "x")?,
+                            y: tmx_number(object, "y")?,
+                            width: tmx_number(object, "width")?,
+                            height: tmx_number(object, "height")?,
+                            properties: tmx_properties(object),
+                        })
+                    })
+                    .collect::<Result<_, String>>()?;
+                layers.push(TiledLayer {
+                    kind: "objectgroup".to_string(),
+                    objects,
+                    properties: tmx_properties(layer),
+                    ..default()
+                });
+            }
+            "imagelayer" => layers.push(TiledLayer {
+                kind: "imagelayer".to_string(),
+                image: layer
+                    .children()
+                    .find(|child| child.has_tag_name("image"))
+                    .and_then(|image| image.attribute("source"))
+                    .unwrap_or_default()
+                    .to_string(),
+                ..default()
+            }),
+            _ => {}
+        }
+    }
+
+    Ok(TiledMap {
+        width: tmx_number(map, "width")? as usize,
+        height: tmx_number(map, "height")? as usize,
+        tilewidth: tmx_number(map, "tilewidth")?,
+        tileheight: tmx_number(map, "tileheight")?,
+        layers,
+        tilesets,
+        properties: tmx_properties(map),
+    })
+}
+
+/// A tileset in a .tmx, or the root of a .tsx
+fn tmx_tileset(tileset: roxmltree::Node) -> Result<TiledTileset, String> {
+    Ok(TiledTileset {
+        firstgid: tmx_number(tileset, "firstgid")? as u32,
+        source: tileset.attribute("source").unwrap_or_default().to_string(),
+        image: tileset
+            .children()
+            .find(|child| child.has_tag_name("image"))
+            .and_then(|image| image.attribute("source"))
+            .unwrap_or_default()
+            .to_string(),
+        tilewidth: tmx_number(tileset, "tilewidth")? as u32,
+        tileheight: tmx_number(tileset, "tileheight")? as u32,
+        columns: tmx_number(tileset, "columns")? as u32,
+        margin: tmx_number(tileset, "margin")? as u32,
+        spacing: tmx_number(tileset, "spacing")? as u32,
+    })
+}
+
+fn tmx_tile_layer(layer: roxmltree::Node) -> Result<TiledLayer, String> {
+    let data = layer
+        .children()
+        .find(|child| child.has_tag_name("data"))
+        .ok_or("A tile layer has no data")?;
+    let data = match data.attribute("encoding") {
+        Some("csv") => data
+            .text()
+            .unwrap_or_default()
+            .split(',')
+            .map(|tile| {
+                tile.trim()
+                    .parse()
+                    .map_err(|_| format!("{} isn't a tile", tile.trim()))
+            })
+            .collect::<Result<_, String>>()?,
+        Some(encoding) => {
+            return Err(format!(
+                "Only CSV tile layers can be imported, not {encoding}"
+            ))
+        }
+        None => data
+            .children()
+            .filter(|child| child.has_tag_name("tile"))
+            .map(|tile| -> Result<u32, String> { Ok(tmx_number(tile, "gid")? as u32) })
+            .collect::<Result<_, String>>()?,
+    };
+
+    Ok(TiledLayer {
+        kind: "tilelayer".to_string(),
+        width: tmx_number(layer, "width")? as usize,
+        data,
+        properties: tmx_properties(layer),
+        ..default()
+    })
+}
+
+/// A number attribute of a .tmx element, 0 if it's not there
+fn tmx_number(node: roxmltree::Node, attribute: &str) -> Result<f32, String> {
+    node.attribute(attribute).map_or(Ok(0.), |value| {
+        value.parse().map_err(|_| {
+            format!(
+                "The {attribute} of a {} isn't a number",
+                node.tag_name().name()
+            )
+        })
+    })
+}
+
+fn tmx_properties(node: roxmltree::Node) -> Vec<TiledProperty> {
+    node.children()
+        .filter(|child| child.has_tag_name("properties"))
+        .flat_map(|properties| properties.children())
+        .filter(|child| child.has_tag_name("property"))
+        .map(|property| {
+            let value = property.attribute("value").unwrap_or_default();
+            TiledProperty {
+                name: property.attribute("name").unwrap_or_default().to_string(),
+                value: match property.attribute("type") {
+                    Some("bool") => Value::Bool(value == "true"),
+                    _ => Value::String(value.to_string()),
+                },
+            }
+        })
+        .collect()
+}
+
+// LDtk
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LdtkProject {
+    levels: Vec<LdtkLevel>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LdtkLevel {
+    identifier: String,
+    px_wid: f32,
+    px_hei: f32,
+    /// Missing if the project saves levels in separate files
+    layer_instances: Option<Vec<LdtkLayer>>,
+    #[serde(default)]
+    bg_rel_path: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LdtkLayer {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__type")]
+    kind: String,
+    #[serde(rename = "__gridSize")]
+    grid_size: f32,
+    #[serde(rename = "__cWid")]
+    columns: usize,
+    #[serde(default)]
+    int_grid_csv: Vec<i64>,
+    #[serde(default)]
+    entity_instances: Vec<LdtkEntity>,
+    /// Relative to the project, only layers with tiles have one
+    #[serde(default, rename = "__tilesetRelPath")]
+    tileset_path: Option<String>,
+    #[serde(default)]
+    grid_tiles: Vec<LdtkTile>,
+    #[serde(default)]
+    auto_layer_tiles: Vec<LdtkTile>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LdtkTile {
+    /// Where it is in the layer
+    px: Vec2,
+    /// Where it is in the tileset
+    src: Vec2,
+    /// 1 if it's flipped horizontally, 2 vertically, 3 both
+    f: u8,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LdtkEntity {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__pivot")]
+    pivot: Vec2,
+    px: Vec2,
+    width: f32,
+    height: f32,
+    #[serde(default)]
+    field_instances: Vec<LdtkField>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LdtkField {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__value")]
+    value: Value,
+}
+
+/// Only the first level of the project is used
+fn ldtk_stage(project: LdtkProject, dir: &Path, file_name: &str) -> Result<StageFile, String> {
+    let level = project
+        .levels
+        .first()
+        .ok_or("The LDtk project has no levels")?;
+    let map_size = Vec2::new(level.px_wid, level.px_hei);
+    let mut objects = MapObjects::default();
+    let mut backgrounds: Vec<_> = level
+        .bg_rel_path
+        .iter()
+        .map(|image| image_layer(image))
+        .collect();
+
+    let layers = level
+        .layer_instances
+        .as_ref()
+        .ok_or("Levels saved in separate files can't be imported")?;
+    // The first layer is the one in front
+    for (index, layer) in layers.iter().enumerate().rev() {
+        if let Some(tiles) = draw_ldtk_layer(layer, dir, map_size)? {
+            backgrounds.push(save_tile_layer(
+                &tiles,
+                &format!("{file_name}_{index}"),
+                map_size,
+            )?);
+        }
+
+        match layer.kind.as_str() {
+            "IntGrid" if normalize(&layer.identifier) == "collision" => {
+                let filled: Vec<bool> = layer.int_grid_csv.iter().map(|cell| *cell != 0).collect();
+                objects.add_grid(
+                    &filled,
+                    layer.columns,
+                    Vec2::splat(layer.grid_size),
+                    map_size,
+                );
+            }
+            "Entities" => {
+                for entity in &layer.entity_instances {
+                    let size = Vec2::new(entity.width, entity.height);
+                    let ledges = entity
+                        .field_instances
+                        .iter()
+                        .find(|field| normalize(&field.identifier) == "ledges")
+                        .and_then(|field| field.value.as_bool())
+                        .unwrap_or(false);
+                    let rect = map_rect(entity.px - entity.pivot * size, size, map_size);
+                    objects.add(&entity.identifier, rect, ledges);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(objects.into_stage(level.identifier.clone(), map_size, backgrounds))
+}
+
+/// The tiles of a layer drawn into an image the size of the level, nothing if it has none
+fn draw_ldtk_layer(
+    layer: &LdtkLayer,
+    dir: &Path,
+    map_size: Vec2,
+) -> Result<Option<RgbaImage>, String> {
+    let Some(tileset_path) = &layer.tileset_path else {
+        return Ok(None);
+    };
+    if layer.grid_tiles.is_empty() && layer.auto_layer_tiles.is_empty() {
+        return Ok(None);
+    }
+
+    let tileset = load_image(&dir.join(tileset_path))?;
+    let mut image = RgbaImage::new(map_size.x as u32, map_size.y as u32);
+    for tile in layer.auto_layer_tiles.iter().chain(&layer.grid_tiles) {
+        draw_tile(
+            &mut image,
+            &tileset,
+            tile.src.as_uvec2(),
+            UVec2::splat(layer.grid_size as u32),
+            tile.px.as_ivec2(),
+            TileFlip {
+                horizontal: tile.f & 1 != 0,
+                vertical: tile.f & 2 != 0,
+                diagonal: false,
+            },
+        );
+    }
+
+    Ok(Some(image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_grid_has_no_rects() {
+        assert!(grid_rects(&[], 4).is_empty());
+        assert!(grid_rects(&[false; 6], 3).is_empty());
+    }
+
+    #[test]
+    fn runs_in_a_row_become_one_rect() {
+        let filled = [true, true, false, true];
+        assert_eq!(
+            grid_rects(&filled, 4),
+            vec![Rect::new(0., 0., 2., 1.), Rect::new(3., 0., 4., 1.)]
+        );
+    }
+
+    #[test]
+    fn same_runs_below_are_merged() {
+        #[rustfmt::skip]
+        let filled = [
+            false, true, true,
+            false, true, true,
+            true, true, true,
+        ];
+        assert_eq!(
+            grid_rects(&filled, 3),
+            vec![Rect::new(1., 0., 3., 2.), Rect::new(0., 2., 3., 3.)]
+        );
+    }
+
+    #[test]
+    fn unfinished_last_row_is_left_out() {
+        let filled = [true, true, true, true, true];
+        assert_eq!(grid_rects(&filled, 2), vec![Rect::new(0., 0., 2., 2.)]);
+    }
+
+    #[test]
+    fn no_columns_has_no_rects() {
+        assert!(grid_rects(&[true, true], 0).is_empty());
+    }
+}
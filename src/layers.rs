@@ -0,0 +1,176 @@
+use bevy_rapier2d::prelude::*;
+
+/// What a collider is, which decides what it collides with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionLayer {
+    Stage,
+    SoftPlatform,
+    /// What keeps characters on the stage
+    CharacterBody,
+    /// Where characters can be hit
+    Hurtbox,
+    /// Attacks, and reflectors and absorbers
+    Hitbox,
+    Projectile,
+    Grabbox,
+    /// Where characters grab onto the stage. Ledges are found from the platforms' corners
+    /// for now, so no collider is on it yet, but characters already collide with it
+    Ledge,
+}
+
+/// What an entity with a collider is.
+/// Every `CollisionGroups` in the game comes from here
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColliderKind {
+    Stage,
+    SoftPlatform,
+    Character,
+    Attack,
+    Grab,
+    /// Reflectors and absorbers
+    Reflector,
+    Projectile,
+    /// Hitboxes of stage hazards
+    Hazard,
+}
+
+/// Which layers interact with each other, it goes both ways
+const INTERACTIONS: [(CollisionLayer, CollisionLayer); 9] = [
+    (CollisionLayer::Stage, CollisionLayer::CharacterBody),
+    (CollisionLayer::Stage, CollisionLayer::Projectile),
+    (CollisionLayer::SoftPlatform, CollisionLayer::CharacterBody),
+    (CollisionLayer::Ledge, CollisionLayer::CharacterBody),
+    (CollisionLayer::Hurtbox, CollisionLayer::Hitbox),
+    (CollisionLayer::Hurtbox, CollisionLayer::Projectile),
+    (CollisionLayer::Hurtbox, CollisionLayer::Grabbox),
+    // Clanks
+    (CollisionLayer::Hitbox, CollisionLayer::Hitbox),
+    // Reflectors and absorbers
+    (CollisionLayer::Hitbox, CollisionLayer::Projectile),
+];
+
+impl CollisionLayer {
+    pub fn group(self) -> Group {
+        match self {
+            CollisionLayer::Stage => Group::GROUP_1,
+            CollisionLayer::SoftPlatform => Group::GROUP_2,
+            CollisionLayer::CharacterBody => Group::GROUP_3,
+            CollisionLayer::Hurtbox => Group::GROUP_4,
+            CollisionLayer::Hitbox => Group::GROUP_5,
+            CollisionLayer::Projectile => Group::GROUP_6,
+            CollisionLayer::Grabbox => Group::GROUP_7,
+            CollisionLayer::Ledge => Group::GROUP_8,
+        }
+    }
+
+    /// Every layer this one interacts with
+    pub fn filter(self) -> Group {
+        INTERACTIONS.iter().fold(Group::NONE, |filter, (a, b)| {
+            match (*a == self, *b == self) {
+                (true, _) => filter | b.group(),
+                (_, true) => filter | a.group(),
+                _ => filter,
+            }
+        })
+    }
+}
+
+impl ColliderKind {
+    pub const ALL: [ColliderKind; 8] = [
+        ColliderKind::Stage,
+        ColliderKind::SoftPlatform,
+        ColliderKind::Character,
+        ColliderKind::Attack,
+        ColliderKind::Grab,
+        ColliderKind::Reflector,
+        ColliderKind::Projectile,
+        ColliderKind::Hazard,
+    ];
+
+    pub fn collision_groups(self) -> CollisionGroups {
+        match self {
+            ColliderKind::Stage => collision_groups(CollisionLayer::Stage),
+            ColliderKind::SoftPlatform => collision_groups(CollisionLayer::SoftPlatform),
+            ColliderKind::Character => character_collision_groups(),
+            // Only attacks clank with each other
+            ColliderKind::Attack => collision_groups_only(
+                CollisionLayer::Hitbox,
+                &[CollisionLayer::Hurtbox, CollisionLayer::Hitbox],
+            ),
+            ColliderKind::Grab => collision_groups(CollisionLayer::Grabbox),
+            // Reflectors and absorbers only care about projectiles
+            ColliderKind::Reflector => {
+                collision_groups_only(CollisionLayer::Hitbox, &[CollisionLayer::Projectile])
+            }
+            ColliderKind::Projectile => collision_groups(CollisionLayer::Projectile),
+            // Only hits characters, so it never clanks
+            ColliderKind::Hazard => {
+                collision_groups_only(CollisionLayer::Hitbox, &[CollisionLayer::Hurtbox])
+            }
+        }
+    }
+
+    /// Whether colliders of the two kinds interact, the same way the physics decides it
+    pub fn interacts_with(self, other: ColliderKind) -> bool {
+        let (a, b) = (self.collision_groups(), other.collision_groups());
+        a.memberships.intersects(b.filters) && b.memberships.intersects(a.filters)
+    }
+}
+
+/// A collider on `layer` that interacts with everything the layer does
+fn collision_groups(layer: CollisionLayer) -> CollisionGroups {
+    CollisionGroups::new(layer.group(), layer.filter())
+}
+
+/// A collider on `layer` that only interacts with some of the layers it could
+fn collision_groups_only(layer: CollisionLayer, only: &[CollisionLayer]) -> CollisionGroups {
+    let only = only
+        .iter()
+        .fold(Group::NONE, |filter, other| filter | other.group());
+    CollisionGroups::new(layer.group(), layer.filter() & only)
+}
+
+/// Characters have a single collider, it's both their body and their hurtbox
+fn character_collision_groups() -> CollisionGroups {
+    let layers = [CollisionLayer::CharacterBody, CollisionLayer::Hurtbox];
+    CollisionGroups::new(
+        layers.iter().fold(Group::NONE, |memberships, layer| {
+            memberships | layer.group()
+        }),
+        layers
+            .iter()
+            .fold(Group::NONE, |filter, layer| filter | layer.filter()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interactions_go_both_ways() {
+        for kind in ColliderKind::ALL {
+            for other in ColliderKind::ALL {
+                assert_eq!(kind.interacts_with(other), other.interacts_with(kind));
+            }
+        }
+    }
+
+    #[test]
+    fn narrowed_hitboxes_only_hit_what_they_care_about() {
+        assert!(ColliderKind::Attack.interacts_with(ColliderKind::Attack));
+        assert!(!ColliderKind::Attack.interacts_with(ColliderKind::Hazard));
+        assert!(!ColliderKind::Reflector.interacts_with(ColliderKind::Character));
+        assert!(ColliderKind::Reflector.interacts_with(ColliderKind::Projectile));
+        assert!(!ColliderKind::Attack.interacts_with(ColliderKind::Projectile));
+    }
+
+    #[test]
+    fn characters_collide_with_ledges() {
+        let groups = ColliderKind::Character.collision_groups();
+        assert!(groups.filters.intersects(CollisionLayer::Ledge.group()));
+        assert!(CollisionLayer::Ledge
+            .filter()
+            .intersects(CollisionLayer::CharacterBody.group()));
+    }
+}
@@ -1,7 +1,13 @@
 mod camera;
 mod character;
 mod editor;
+mod hazard;
+mod import;
+mod layers;
+mod mask;
+mod ruleset;
 mod stage;
+mod stage_select;
 mod ui;
 
 use bevy::prelude::*;
@@ -10,6 +16,9 @@ use bevy_egui::EguiPlugin;
 use bevy::window::Window;
 use bevy_rapier2d::prelude::*;
 
+/// Where the asset server loads from, files written for it go in here too
+const ASSETS_DIRECTORY: &str = "assets";
+
 fn setup_window(mut window: Query<&mut Window>) {
     let mut window = window.single_mut();
     window.title = "SUwUssy PeidrOwO".to_string();
@@ -19,6 +28,7 @@ fn setup_window(mut window: Query<&mut Window>) {
 #[derive(States, PartialEq, Eq, Debug, Clone, Hash, Default)]
 enum GameStates {
     #[default]
+    StageSelect,
     Playing,
     Editor,
 }
@@ -27,14 +37,21 @@ fn main() {
     App::new()
         .add_startup_system(setup_window)
         .add_plugins(
-            DefaultPlugins, // .build()
-                            // .add_before::<bevy::asset::AssetPlugin, _>(EmbeddedAssetPlugin),
+            DefaultPlugins.set(bevy::asset::AssetPlugin {
+                asset_folder: ASSETS_DIRECTORY.to_string(),
+                ..default()
+            }),
+            // .build()
+            // .add_before::<bevy::asset::AssetPlugin, _>(EmbeddedAssetPlugin),
         )
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+        .add_plugin(RapierPhysicsPlugin::<stage::StageHookData>::default())
         .add_plugin(RapierDebugRenderPlugin::default())
+        .add_plugin(ruleset::RulesetPlugin)
         .add_plugin(EguiPlugin)
         .add_plugin(camera::CameraPlugin)
         .add_plugin(stage::StagePlugin)
+        .add_plugin(stage_select::StageSelectPlugin)
+        .add_plugin(hazard::HazardPlugin)
         .add_plugin(character::CharacterPlugin)
         .add_plugin(ui::UiPlugin)
         .add_plugin(editor::EditorPlugin)
@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::stage::{PlatformDefinition, PlatformShape};
+
+/// How to make platforms out of a sprite
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaskOptions {
+    /// Relative to `assets`
+    pub sprite: String,
+    /// Pixels less opaque than this, from 0. to 1., are left out
+    pub alpha_cutoff: f32,
+    /// How far the outlines can stray from the pixels to have fewer points, in pixels
+    pub tolerance: f32,
+    /// How big a pixel is in the stage
+    pub scale: f32,
+    /// Where the center of the sprite is in the stage
+    pub position: Vec2,
+}
+
+impl Default for MaskOptions {
+    fn default() -> Self {
+        Self {
+            sprite: String::new(),
+            alpha_cutoff: 0.5,
+            tolerance: 1.,
+            scale: 1.,
+            position: Vec2::ZERO,
+        }
+    }
+}
+
+/// Solid platforms outlining the opaque parts of the sprite.
+/// Convex parts become polygons, the rest become outlines along with their holes
+pub fn mask_platforms(options: &MaskOptions) -> Result<Vec<PlatformDefinition>, String> {
+    let path = format!("assets/{}", options.sprite);
+    let image = image::open(&path)
+        .map_err(|error| format!("Couldn't open {path}: {error}"))?
+        .to_rgba8();
+    let (width, height) = image.dimensions();
+    let size = Vec2::new(width as f32, height as f32);
+
+    let filled: Vec<bool> = image
+        .pixels()
+        .map(|pixel| pixel.0[3] as f32 / 255. > options.alpha_cutoff)
+        .collect();
+
+    let outlines: Vec<Vec<Vec2>> = contours(&filled, width as usize)
+        .iter()
+        .map(|contour| simplify_loop(contour, options.tolerance))
+        .filter(|outline| outline.len() >= 3)
+        .collect();
+
+    // Outlines go around filled pixels one way and around holes the other way
+    let (solids, holes): (Vec<_>, Vec<_>) = outlines
+        .iter()
+        .partition(|outline| signed_area(outline) < 0.);
+    let mut solid_holes = vec![Vec::new(); solids.len()];
+    for hole in holes {
+        // The smallest outline around the hole is the one it's cut out of,
+        // any bigger one is around that outline too
+        let parent = solids
+            .iter()
+            .enumerate()
+            .filter(|(_, solid)| contains(solid, hole[0]))
+            .min_by(|(_, a), (_, b)| signed_area(a).abs().total_cmp(&signed_area(b).abs()));
+        if let Some((index, _)) = parent {
+            solid_holes[index].push(hole);
+        }
+    }
+
+    Ok(solids
+        .iter()
+        .zip(solid_holes)
+        .map(|(outline, holes)| {
+            // Pixels go down from the top left corner, the stage goes up from the center
+            let to_stage = |point: Vec2| (point - size / 2.) * Vec2::new(1., -1.) * options.scale;
+            let center = to_stage(bounds(outline).center());
+            let to_platform = |points: &Vec<Vec2>| -> Vec<Vec2> {
+                points
+                    .iter()
+                    .map(|point| to_stage(*point) - center)
+                    .collect()
+            };
+
+            let mut platform = PlatformDefinition {
+                position: options.position + center,
+                half_extents: Vec2::ZERO,
+                // A polygon would fill in the holes, like the inside of a ring
+                shape: if is_convex(outline) && holes.is_empty() {
+                    PlatformShape::Polygon(to_platform(outline))
+                } else {
+                    PlatformShape::Polyline {
+                        outline: to_platform(outline),
+                        holes: holes.into_iter().map(to_platform).collect(),
+                    }
+                },
+                soft: false,
+                ledges: false,
+                path: None,
+            };
+            platform.fit_to_shape();
+            platform
+        })
+        .collect())
+}
+
+/// The outlines between filled and empty pixels, with marching squares.
+/// `filled` is row by row from the top, and the points are in pixels with y down.
+/// Pixels only touching at a corner are apart
+fn contours(filled: &[bool], width: usize) -> Vec<Vec<Vec2>> {
+    let height = filled.len() / width.max(1);
+    // Samples are pixel centers, with an empty border around the image
+    let sample = |x: i32, y: i32| {
+        x >= 1
+            && y >= 1
+            && x as usize <= width
+            && y as usize <= height
+            && filled[(y as usize - 1) * width + x as usize - 1]
+    };
+
+    // Where each outline segment goes, from the middle of an edge between samples to another.
+    // Points are in half samples, so the middles are whole numbers
+    let mut segments = BTreeMap::new();
+    for y in 0..=height as i32 {
+        for x in 0..=width as i32 {
+            // Clockwise, with y down
+            let corners = [
+                IVec2::new(x, y),
+                IVec2::new(x + 1, y),
+                IVec2::new(x + 1, y + 1),
+                IVec2::new(x, y + 1),
+            ];
+            let values = corners.map(|corner| sample(corner.x, corner.y));
+            let middle = |edge: usize| {
+                let middle = corners[edge] + corners[(edge + 1) % 4];
+                (middle.x, middle.y)
+            };
+
+            // From where the outline comes into the filled corners to where it leaves them,
+            // so the filled side is always the same
+            for entering in 0..4 {
+                if values[entering] || !values[(entering + 1) % 4] {
+                    continue;
+                }
+                let mut leaving = (entering + 1) % 4;
+                while !values[leaving] || values[(leaving + 1) % 4] {
+                    leaving = (leaving + 1) % 4;
+                }
+                segments.insert(middle(entering), middle(leaving));
+            }
+        }
+    }
+
+    let mut contours = Vec::new();
+    while let Some((&start, _)) = segments.iter().next() {
+        let mut contour = Vec::new();
+        let mut point = start;
+        while let Some(next) = segments.remove(&point) {
+            // Back from half samples to pixels
+            contour.push((Vec2::new(point.0 as f32, point.1 as f32) - 1.) / 2.);
+            point = next;
+        }
+        contours.push(contour);
+    }
+
+    contours
+}
+
+/// The outline with fewer points, none further than `tolerance` from where it was,
+/// with Ramer-Douglas-Peucker. The first and last points are kept
+fn simplify(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+        return Vec::new();
+    };
+    if points.len() == 1 {
+        return vec![first];
+    }
+
+    let furthest = points
+        .iter()
+        .map(|point| segment_distance(*point, first, last))
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    match furthest {
+        Some((index, distance)) if distance > tolerance => {
+            let mut simplified = simplify(&points[..=index], tolerance);
+            simplified.pop();
+            simplified.extend(simplify(&points[index..], tolerance));
+            simplified
+        }
+        _ => vec![first, last],
+    }
+}
+
+/// Like `simplify`, for an outline that ends where it starts
+fn simplify_loop(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    let Some(&first) = points.first() else {
+        return Vec::new();
+    };
+
+    // Split it where it's furthest from the start, so both halves have ends to keep
+    let furthest = points
+        .iter()
+        .map(|point| point.distance_squared(first))
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0, |(index, _)| index);
+    if furthest == 0 {
+        return vec![first];
+    }
+
+    let mut simplified = simplify(&points[..=furthest], tolerance);
+    simplified.pop();
+    let back = [&points[furthest..], &points[..1]].concat();
+    let back = simplify(&back, tolerance);
+    simplified.extend(&back[..back.len() - 1]);
+    simplified
+}
+
+/// How far the point is from the segment between `start` and `end`
+fn segment_distance(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let segment = end - start;
+    let t = if segment == Vec2::ZERO {
+        0.
+    } else {
+        ((point - start).dot(segment) / segment.length_squared()).clamp(0., 1.)
+    };
+    point.distance(start + segment * t)
+}
+
+/// Whether the outline turns the same way at every point
+fn is_convex(points: &[Vec2]) -> bool {
+    let turns = (0..points.len()).map(|index| {
+        let [a, b, c] = [0, 1, 2].map(|offset| points[(index + offset) % points.len()]);
+        (b - a).perp_dot(c - b)
+    });
+    let (mut left, mut right) = (false, false);
+    for turn in turns {
+        left |= turn > 0.;
+        right |= turn < 0.;
+    }
+    !(left && right)
+}
+
+/// Positive when the outline goes counterclockwise with y up, clockwise with y down
+fn signed_area(outline: &[Vec2]) -> f32 {
+    (0..outline.len())
+        .map(|index| outline[index].perp_dot(outline[(index + 1) % outline.len()]))
+        .sum::<f32>()
+        / 2.
+}
+
+/// Whether the point is inside the outline, from how many of its edges a ray to the right crosses
+fn contains(outline: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for index in 0..outline.len() {
+        let (a, b) = (outline[index], outline[(index + 1) % outline.len()]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn bounds(points: &[Vec2]) -> Rect {
+    points.iter().fold(
+        Rect::from_center_size(points[0], Vec2::ZERO),
+        |bounds, point| bounds.union_point(*point),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_filled_has_no_contours() {
+        assert!(contours(&[], 3).is_empty());
+        assert!(contours(&[false; 4], 2).is_empty());
+    }
+
+    #[test]
+    fn a_pixel_is_outlined_around_its_center() {
+        let contours = contours(&[true], 1);
+        assert_eq!(contours.len(), 1);
+        let outline = bounds(&contours[0]);
+        assert_eq!(outline, Rect::new(0., 0., 1., 1.));
+    }
+
+    #[test]
+    fn pixels_touching_at_a_corner_are_apart() {
+        assert_eq!(contours(&[true, false, false, true], 2).len(), 2);
+    }
+
+    #[test]
+    fn holes_have_their_own_contour() {
+        #[rustfmt::skip]
+        let filled = [
+            true, true, true,
+            true, false, true,
+            true, true, true,
+        ];
+        assert_eq!(contours(&filled, 3).len(), 2);
+    }
+
+    #[test]
+    fn holes_wind_the_other_way() {
+        #[rustfmt::skip]
+        let filled = [
+            true, true, true,
+            true, false, true,
+            true, true, true,
+        ];
+        let mut areas: Vec<f32> = contours(&filled, 3)
+            .iter()
+            .map(|contour| signed_area(contour))
+            .collect();
+        areas.sort_by(f32::total_cmp);
+        // The outside is bigger than the hole, and they go opposite ways
+        assert!(areas[0] < 0. && areas[1] > 0.);
+        assert!(-areas[0] > areas[1]);
+        assert!(signed_area(&contours(&[true], 1)[0]) < 0.);
+    }
+
+    #[test]
+    fn containment_follows_the_outline_not_its_bounds() {
+        let l_shape = [
+            Vec2::ZERO,
+            Vec2::new(2., 0.),
+            Vec2::new(2., 1.),
+            Vec2::new(1., 1.),
+            Vec2::new(1., 2.),
+            Vec2::new(0., 2.),
+        ];
+        assert!(contains(&l_shape, Vec2::new(0.5, 1.5)));
+        assert!(contains(&l_shape, Vec2::new(1.5, 0.5)));
+        assert!(!contains(&l_shape, Vec2::new(1.5, 1.5)));
+    }
+
+    #[test]
+    fn straight_lines_keep_their_ends() {
+        let line: Vec<Vec2> = (0..10).map(|x| Vec2::new(x as f32, 0.)).collect();
+        assert_eq!(simplify(&line, 0.1), vec![Vec2::ZERO, Vec2::new(9., 0.)]);
+    }
+
+    #[test]
+    fn corners_further_than_the_tolerance_are_kept() {
+        let corner = [Vec2::ZERO, Vec2::new(5., 0.), Vec2::new(5., 5.)];
+        assert_eq!(simplify(&corner, 1.), corner);
+        assert_eq!(simplify(&corner, 10.), vec![corner[0], corner[2]]);
+    }
+
+    #[test]
+    fn filled_square_becomes_four_corners() {
+        let filled = [true; 16];
+        let contour = &contours(&filled, 4)[0];
+        // Marching squares cuts the corners a bit, so they need some tolerance
+        assert_eq!(simplify_loop(contour, 0.1).len(), 8);
+        let outline = simplify_loop(contour, 1.);
+        assert_eq!(outline.len(), 4);
+        assert!(is_convex(&outline));
+    }
+
+    #[test]
+    fn l_shape_is_not_convex() {
+        let outline = [
+            Vec2::ZERO,
+            Vec2::new(2., 0.),
+            Vec2::new(2., 1.),
+            Vec2::new(1., 1.),
+            Vec2::new(1., 2.),
+            Vec2::new(0., 2.),
+        ];
+        assert!(!is_convex(&outline));
+    }
+}
@@ -0,0 +1,259 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::character::Character;
+
+const RULESET_FILE: &str = "assets/rulesets/default.json";
+
+pub struct RulesetPlugin;
+
+impl Plugin for RulesetPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Ruleset::load(RULESET_FILE).unwrap_or_else(|error| {
+            error!("{error}, using the default ruleset");
+            Ruleset::default()
+        }))
+        .add_system(apply_handicaps);
+    }
+}
+
+/// The rules of the match, read from a file in `assets/rulesets`
+#[derive(Serialize, Deserialize, Resource, Debug, Clone)]
+#[serde(default)]
+pub struct Ruleset {
+    pub stale_moves: StaleMoveRules,
+
+    /// All damage is multiplied by this
+    pub damage_ratio: f32,
+
+    /// Knockback gets stronger the more damage the attacker has
+    pub rage: Option<Rage>,
+
+    /// By player slot, starting at 0. Characters without one play normally
+    pub handicaps: HashMap<usize, Handicap>,
+
+    /// Whether stages have their hazards and transformations
+    pub hazards: bool,
+
+    /// How fast characters standing inside each other get pushed apart,
+    /// in pixels per second. 0 turns pushing off
+    pub push_strength: f32,
+
+    pub stages: StageList,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Rage {
+    /// The percentage rage starts at
+    pub start: f32,
+    /// The percentage rage is at its strongest
+    pub end: f32,
+    /// What knockback gets multiplied by at the strongest
+    pub max_multiplier: f32,
+}
+
+/// Handicaps of a character, given to it when it spawns
+#[derive(Serialize, Deserialize, Component, Debug, Clone)]
+#[serde(default)]
+pub struct Handicap {
+    pub starting_percentage: f32,
+    /// What the damage the character takes gets multiplied by
+    pub damage_multiplier: f32,
+}
+
+/// How much repeating the same move weakens it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StaleMoveRules {
+    pub enabled: bool,
+
+    /// How much each spot in the queue of the last moves that connected
+    /// takes off a move in it, newest first. Its length is the size of the queue
+    pub queue_factors: Vec<f32>,
+
+    /// The multiplier of moves that aren't in the queue at all
+    pub fresh_bonus: f32,
+}
+
+/// Which stages can be played, by file name in `assets/stages` without the extension
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct StageList {
+    /// Can be picked for the first game. Empty means every stage
+    pub starters: Vec<String>,
+    /// Can also be picked after the first game
+    pub counterpicks: Vec<String>,
+
+    /// Whether the first game's stage is struck down to one, instead of picked
+    pub strikes: bool,
+    /// How many stages the winner bans before a counterpick
+    pub bans: usize,
+}
+
+impl Ruleset {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = std::fs::read_to_string(path)
+            .map_err(|error| format!("Couldn't read {path}: {error}"))?;
+        serde_json::from_str(&file)
+            .map_err(|error| format!("{path} isn't a valid ruleset: {error}"))
+    }
+
+    /// The damage a character with `handicap` takes from a hit that deals `damage`
+    pub fn damage_taken(&self, damage: f32, handicap: &Handicap) -> f32 {
+        damage * self.damage_ratio * handicap.damage_multiplier
+    }
+
+    /// What the knockback of an attacker with `percentage` damage gets multiplied by
+    pub fn rage_multiplier(&self, percentage: f32) -> f32 {
+        let Some(rage) = &self.rage else {
+            return 1.;
+        };
+
+        // Without any room to grow, rage kicks in all at once at the end
+        let t = if rage.end <= rage.start {
+            if percentage >= rage.end {
+                1.
+            } else {
+                0.
+            }
+        } else {
+            ((percentage - rage.start) / (rage.end - rage.start)).clamp(0., 1.)
+        };
+        1. + (rage.max_multiplier - 1.) * t
+    }
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self {
+            stale_moves: default(),
+            damage_ratio: 1.,
+            rage: None,
+            handicaps: default(),
+            hazards: true,
+            push_strength: 300.,
+            stages: default(),
+        }
+    }
+}
+
+impl StageList {
+    pub fn is_starter(&self, stage: &str) -> bool {
+        self.starters.is_empty() || self.starters.iter().any(|starter| starter == stage)
+    }
+
+    pub fn is_counterpick(&self, stage: &str) -> bool {
+        self.counterpicks
+            .iter()
+            .any(|counterpick| counterpick == stage)
+    }
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Self {
+            starting_percentage: 0.,
+            damage_multiplier: 1.,
+        }
+    }
+}
+
+impl Default for StaleMoveRules {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            queue_factors: vec![0.09, 0.08, 0.07, 0.06, 0.05, 0.04, 0.03, 0.02, 0.01],
+            fresh_bonus: 1.05,
+        }
+    }
+}
+
+/// Gives new characters their handicap from the ruleset
+fn apply_handicaps(
+    mut character_query: Query<(&mut Character, &mut Handicap), Added<Character>>,
+    ruleset: Res<Ruleset>,
+) {
+    for (mut character, mut handicap) in character_query.iter_mut() {
+        let Some(ruleset_handicap) = ruleset.handicaps.get(&character.slot) else {
+            continue;
+        };
+
+        *handicap = ruleset_handicap.clone();
+        character.reset_damage(&handicap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ruleset_with_rage() -> Ruleset {
+        Ruleset {
+            rage: Some(Rage {
+                start: 35.,
+                end: 150.,
+                max_multiplier: 1.1,
+            }),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn no_rage_never_changes_knockback() {
+        let ruleset = Ruleset::default();
+        assert_eq!(ruleset.rage_multiplier(0.), 1.);
+        assert_eq!(ruleset.rage_multiplier(999.), 1.);
+    }
+
+    #[test]
+    fn rage_grows_between_start_and_end() {
+        let ruleset = ruleset_with_rage();
+
+        assert_eq!(ruleset.rage_multiplier(0.), 1.);
+        assert_eq!(ruleset.rage_multiplier(35.), 1.);
+        assert!((ruleset.rage_multiplier(92.5) - 1.05).abs() < 1e-6);
+        assert!((ruleset.rage_multiplier(150.) - 1.1).abs() < 1e-6);
+        assert!((ruleset.rage_multiplier(300.) - 1.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rage_starting_at_its_end_is_a_step() {
+        let ruleset = Ruleset {
+            rage: Some(Rage {
+                start: 100.,
+                end: 100.,
+                max_multiplier: 1.1,
+            }),
+            ..default()
+        };
+
+        assert_eq!(ruleset.rage_multiplier(99.9), 1.);
+        assert!((ruleset.rage_multiplier(100.) - 1.1).abs() < 1e-6);
+        assert!((ruleset.rage_multiplier(200.) - 1.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn damage_taken_uses_the_ratio_and_the_handicap() {
+        let ruleset = Ruleset {
+            damage_ratio: 2.,
+            ..default()
+        };
+        let handicap = Handicap {
+            damage_multiplier: 0.5,
+            ..default()
+        };
+
+        assert_eq!(ruleset.damage_taken(10., &handicap), 10.);
+        assert_eq!(ruleset.damage_taken(10., &Handicap::default()), 20.);
+    }
+
+    #[test]
+    fn handicaps_are_read_by_slot() {
+        let ruleset: Ruleset =
+            serde_json::from_str(r#"{ "handicaps": { "1": { "starting_percentage": 50.0 } } }"#)
+                .unwrap();
+
+        assert_eq!(ruleset.handicaps[&1].starting_percentage, 50.);
+        assert!(!ruleset.handicaps.contains_key(&0));
+    }
+}
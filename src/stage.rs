@@ -1,35 +1,757 @@
+use std::path::PathBuf;
+
 use bevy::prelude::*;
-use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::{prelude::*, rapier::math::Vector};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    character::CharacterMovement,
+    hazard::{HazardDefinition, StageTransformation},
+    layers::ColliderKind,
+    GameStates,
+};
+
+pub const STAGES_DIRECTORY: &str = "assets/stages";
+const STAGE_FILE: &str = "assets/stages/battlefield.json";
+
+/// Behind everything else
+const BACKGROUND_Z: f32 = -10.;
+/// How far apart background layers are, each one is in front of the one before it
+const BACKGROUND_LAYER_SPACING: f32 = 0.01;
+/// The world's gravity before the stage's physics profile, in units per second squared.
+/// The gravity scales of the characters multiply it
+const GRAVITY: f32 = 9.81;
+
+/// How far (in radians) the contact normal can be from straight up
+/// for a soft platform to still hold a character
+const SOFT_PLATFORM_ALLOWED_ANGLE: f32 = 0.3;
+
+/// Same value rapier uses in `update_as_oneway_platform`
+/// to keep ignoring a contact until the bodies separate
+const SOFT_CONTACT_FORBIDDEN: u32 = 2;
 
 pub struct StagePlugin;
 
 impl Plugin for StagePlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup_stage);
+        app.insert_resource(startup_stage())
+            .add_startup_system(setup_stage)
+            .add_system(respawn_stage)
+            .add_system(parallax_backgrounds)
+            .add_system(apply_physics_profile)
+            .add_system(move_platforms.in_set(OnUpdate(GameStates::Playing)))
+            .insert_resource(PhysicsHooksWithQueryResource::<StageHookData>(Box::new(
+                SoftPlatformHooks,
+            )));
+    }
+}
+
+/// A stage, read from a file in `assets/stages`
+#[derive(Serialize, Deserialize, Resource, Debug, Clone)]
+pub struct StageFile {
+    /// Where it was loaded from, saving writes it back there
+    #[serde(skip)]
+    pub path: String,
+
+    pub name: String,
+    pub platforms: Vec<PlatformDefinition>,
+
+    /// Where the characters start the match, one for each
+    pub spawn_points: Vec<Vec2>,
+    /// Where the characters come back after being KO'd
+    pub respawn_points: Vec<Vec2>,
+
+    /// Characters that leave it are KO'd
+    pub blast_zone: Rect,
+    /// The camera never shows anything outside of it
+    pub camera_bounds: Rect,
+
+    /// Drawn behind the stage, the first one at the back
+    #[serde(default)]
+    pub backgrounds: Vec<BackgroundLayer>,
+    /// Looped while on the stage, relative to `assets`
+    #[serde(default)]
+    pub music: Option<String>,
+    /// Shown in the stage select, relative to `assets`
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+
+    /// Only there if the ruleset has hazards on
+    #[serde(default)]
+    pub hazards: Vec<HazardDefinition>,
+    #[serde(default)]
+    pub transformation: Option<StageTransformation>,
+
+    #[serde(default)]
+    pub physics: PhysicsProfile,
+}
+
+/// How physics feel on a stage, like low gravity arenas
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PhysicsProfile {
+    /// What the world's gravity gets multiplied by
+    pub gravity_multiplier: f32,
+    /// How many pixels a unit is. Speeds, jumps, knockback and gravity are in units,
+    /// while the stage itself, its blast zone, hazards and moving platforms are in pixels
+    pub pixels_per_unit: f32,
+    /// The linear damping characters have while in the air
+    pub air_friction: f32,
+}
+
+/// An image behind the stage
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackgroundLayer {
+    /// Relative to `assets`
+    pub image: String,
+    /// Where it's centered, the center of the camera bounds if there's none
+    #[serde(default)]
+    pub position: Option<Vec2>,
+    /// The size of the camera bounds if there's none
+    #[serde(default)]
+    pub size: Option<Vec2>,
+    /// How much it follows the camera. 0 stays with the stage,
+    /// 1 stays with the camera and anything in between looks far away
+    #[serde(default)]
+    pub parallax: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlatformDefinition {
+    pub position: Vec2,
+    /// The box around the platform. For polygons it's worked out from the points on load
+    #[serde(default)]
+    pub half_extents: Vec2,
+
+    #[serde(default)]
+    pub shape: PlatformShape,
+
+    /// Soft platforms are only solid from above
+    #[serde(default)]
+    pub soft: bool,
+
+    /// Whether characters can hang from its top corners
+    #[serde(default)]
+    pub ledges: bool,
+
+    /// Platforms with a path move along it, the rest don't move
+    #[serde(default)]
+    pub path: Option<PlatformPath>,
+}
+
+/// The collider of a platform, around its position
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum PlatformShape {
+    /// Fills `half_extents`
+    #[default]
+    Box,
+    /// The convex hull of the points, relative to the position
+    Polygon(Vec<Vec2>),
+    /// The outline through the points, relative to the position. The last point connects
+    /// back to the first, so it can be any shape, but only its outline is solid
+    Polyline {
+        outline: Vec<Vec2>,
+        /// Outlines of the holes inside, closed the same way
+        #[serde(default)]
+        holes: Vec<Vec<Vec2>>,
+    },
+}
+
+impl PlatformShape {
+    /// The closed loops of points, the outline first and then its holes. Empty for boxes
+    pub fn loops(&self) -> impl Iterator<Item = &Vec<Vec2>> {
+        let (outline, holes) = match self {
+            PlatformShape::Box => (None, &[][..]),
+            PlatformShape::Polygon(points) => (Some(points), &[][..]),
+            PlatformShape::Polyline { outline, holes } => (Some(outline), &holes[..]),
+        };
+        outline.into_iter().chain(holes)
+    }
+
+    /// Empty for boxes
+    pub fn points(&self) -> impl Iterator<Item = &Vec2> {
+        self.loops().flatten()
+    }
+
+    fn points_mut(&mut self) -> impl Iterator<Item = &mut Vec2> {
+        let (outline, holes) = match self {
+            PlatformShape::Box => (None, &mut [][..]),
+            PlatformShape::Polygon(points) => (Some(points), &mut [][..]),
+            PlatformShape::Polyline { outline, holes } => (Some(outline), &mut holes[..]),
+        };
+        outline.into_iter().chain(holes).flatten()
+    }
+}
+
+/// Where a moving platform goes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlatformPath {
+    /// Sorted by time, the first one is usually at 0.
+    /// Looping paths should end where they start, or the platform jumps back
+    pub keyframes: Vec<Keyframe>,
+    #[serde(default)]
+    pub mode: PathMode,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Keyframe {
+    /// Seconds since the path started
+    pub time: f32,
+    /// From the platform's position
+    pub offset: Vec2,
+}
+
+/// What happens when a platform gets to the end of its path
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathMode {
+    /// Stays there
+    #[default]
+    Once,
+    /// Starts over
+    Loop,
+    /// Goes back the same way
+    PingPong,
+}
+
+/// How platforms move between keyframes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    /// At a constant speed
+    #[default]
+    Linear,
+    /// Slowing down at the keyframes
+    EaseInOut,
+}
+
+impl PlatformPath {
+    /// Where the platform is `time` seconds after starting, from its position
+    pub fn offset_at(&self, time: f32) -> Vec2 {
+        let (Some(first), Some(last)) = (self.keyframes.first(), self.keyframes.last()) else {
+            return Vec2::ZERO;
+        };
+        let duration = last.time;
+        if duration <= 0. {
+            return first.offset;
+        }
+
+        let time = match self.mode {
+            PathMode::Once => time.min(duration),
+            PathMode::Loop => time.rem_euclid(duration),
+            PathMode::PingPong => {
+                let time = time.rem_euclid(duration * 2.);
+                if time > duration {
+                    duration * 2. - time
+                } else {
+                    time
+                }
+            }
+        };
+
+        let (from, to) = match self.keyframes.iter().position(|key| key.time > time) {
+            Some(0) => return first.offset,
+            Some(next) => (&self.keyframes[next - 1], &self.keyframes[next]),
+            None => return last.offset,
+        };
+
+        let t = (time - from.time) / (to.time - from.time);
+        let t = match self.easing {
+            Easing::Linear => t,
+            Easing::EaseInOut => t * t * (3. - 2. * t),
+        };
+        from.offset.lerp(to.offset, t)
+    }
+}
+
+impl Default for PhysicsProfile {
+    fn default() -> Self {
+        Self {
+            gravity_multiplier: 1.,
+            pixels_per_unit: 1.,
+            air_friction: 0.,
+        }
+    }
+}
+
+impl PhysicsProfile {
+    /// The gravity of the world, in pixels per second squared
+    pub fn gravity(&self) -> Vec2 {
+        Vec2::NEG_Y * GRAVITY * self.gravity_multiplier * self.pixels_per_unit
+    }
+}
+
+impl PlatformDefinition {
+    pub fn collider(&self) -> Collider {
+        let cuboid = || Collider::cuboid(self.half_extents.x, self.half_extents.y);
+        match &self.shape {
+            PlatformShape::Box => cuboid(),
+            // Fewer than 3 points in a line don't make a hull
+            PlatformShape::Polygon(points) => Collider::convex_hull(points).unwrap_or_else(cuboid),
+            PlatformShape::Polyline { outline, .. } if outline.len() >= 2 => {
+                // Every loop is closed on its own, holes don't connect to the outline
+                let mut vertices = Vec::new();
+                let mut segments = Vec::new();
+                for points in self.shape.loops() {
+                    let (start, count) = (vertices.len() as u32, points.len() as u32);
+                    segments.extend(
+                        (0..count).map(|index| [start + index, start + (index + 1) % count]),
+                    );
+                    vertices.extend(points);
+                }
+                Collider::polyline(vertices, Some(segments))
+            }
+            PlatformShape::Polyline { .. } => cuboid(),
+        }
+    }
+
+    /// Makes `half_extents` the box around the shape's points
+    pub fn fit_to_shape(&mut self) {
+        if self.shape != PlatformShape::Box {
+            self.half_extents = self.shape.points().fold(Vec2::ZERO, |half_extents, point| {
+                half_extents.max(point.abs())
+            });
+        }
+    }
+
+    /// Scales the shape along with the box, like when it's resized in the editor
+    pub fn resize(&mut self, half_extents: Vec2) {
+        let scale = half_extents / self.half_extents.max(Vec2::splat(f32::EPSILON));
+        for point in self.shape.points_mut() {
+            *point *= scale;
+        }
+        self.half_extents = half_extents;
+    }
+}
+
+impl StageFile {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = std::fs::read_to_string(path)
+            .map_err(|error| format!("Couldn't read {path}: {error}"))?;
+        let mut stage: Self = serde_json::from_str(&file)
+            .map_err(|error| format!("{path} isn't a valid stage: {error}"))?;
+        let layouts = stage
+            .transformation
+            .iter_mut()
+            .flat_map(|transformation| transformation.layouts.iter_mut());
+        for platform in stage.platforms.iter_mut().chain(layouts.flatten()) {
+            platform.fit_to_shape();
+        }
+        Ok(Self {
+            path: path.to_string(),
+            ..stage
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| error.to_string())?;
+        std::fs::write(&self.path, json)
+            .map_err(|error| format!("Couldn't save to {}: {error}", self.path))
+    }
+
+    /// The spawn point of the `index`th character, they wrap around if there are too few.
+    /// The origin if there are none
+    pub fn spawn_point(&self, index: usize) -> Vec2 {
+        wrapping_point(&self.spawn_points, index)
+    }
+
+    /// Respawn points are also used in turns
+    pub fn respawn_point(&self, index: usize) -> Vec2 {
+        wrapping_point(&self.respawn_points, index)
+    }
+}
+
+/// A floor to stand on, for when no stage can be loaded
+impl Default for StageFile {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            name: "Empty".to_string(),
+            platforms: vec![PlatformDefinition {
+                position: Vec2::new(0., -1000.),
+                half_extents: Vec2::new(500., 500.),
+                shape: PlatformShape::Box,
+                soft: false,
+                ledges: true,
+                path: None,
+            }],
+            spawn_points: vec![Vec2::new(-250., -400.), Vec2::new(250., -400.)],
+            respawn_points: vec![Vec2::new(0., 300.)],
+            blast_zone: Rect::new(-2000., -2500., 2000., 1500.),
+            camera_bounds: Rect::new(-1600., -2000., 1600., 1100.),
+            backgrounds: Vec::new(),
+            music: None,
+            thumbnail: None,
+            hazards: Vec::new(),
+            transformation: None,
+            physics: default(),
+        }
+    }
+}
+
+/// The stage files in `STAGES_DIRECTORY`, sorted by name
+pub fn stage_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<_> = match std::fs::read_dir(STAGES_DIRECTORY) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |extension| extension == "json")
+            })
+            .collect(),
+        Err(error) => {
+            warn!("Couldn't read {STAGES_DIRECTORY}: {error}");
+            Vec::new()
+        }
+    };
+    paths.sort();
+    paths
+}
+
+/// The stage the game starts on. If it can't be loaded, the first stage that can,
+/// and an empty stage if none of them can
+fn startup_stage() -> StageFile {
+    match StageFile::load(STAGE_FILE) {
+        Ok(stage) => return stage,
+        Err(error) => error!("{error}"),
+    }
+
+    for path in stage_paths() {
+        match StageFile::load(&path.to_string_lossy()) {
+            Ok(stage) => {
+                warn!("Starting on {} instead", stage.path);
+                return stage;
+            }
+            Err(error) => warn!("{error}"),
+        }
+    }
+
+    error!("No stage could be loaded, starting on an empty one");
+    StageFile::default()
+}
+
+fn wrapping_point(points: &[Vec2], index: usize) -> Vec2 {
+    if points.is_empty() {
+        Vec2::ZERO
+    } else {
+        points[index % points.len()]
     }
 }
 
 #[derive(Component)]
 pub struct Stage;
 
-fn setup_stage(mut commands: Commands) {
-    commands.spawn((
+/// Anything spawned from the stage file
+#[derive(Component)]
+pub struct StageElement;
+
+/// A background layer, moved along with the camera by its parallax
+#[derive(Component, Debug, Clone)]
+struct Background {
+    position: Vec2,
+    parallax: f32,
+}
+
+/// A platform following a path, characters standing on it move along with it
+#[derive(Component, Debug, Clone)]
+pub struct MovingPlatform {
+    path: PlatformPath,
+    /// The platform's position in the stage file
+    origin: Vec2,
+    time: f32,
+
+    /// How fast the platform moved in the last tick
+    pub velocity: Vec2,
+}
+
+/// A platform that is only solid from above.
+/// Characters can jump up through it and drop down through it.
+/// Must also have `Stage`, so standing on it counts as being on stage
+#[derive(Component)]
+pub struct SoftPlatform;
+
+/// A corner of the stage characters can hang from.
+/// Doesn't have a collider, characters grab it when it's inside their `LedgeGrabber` volume
+#[derive(Component)]
+pub struct Ledge {
+    /// Which side the ledge faces, -1. for left and 1. for right.
+    /// The stage is on the opposite side
+    pub direction: f32,
+}
+
+/// What the physics hooks can see about the colliders
+pub type StageHookData = (
+    Option<&'static SoftPlatform>,
+    Option<&'static CharacterMovement>,
+);
+
+struct SoftPlatformHooks;
+
+impl PhysicsHooksWithQuery<StageHookData> for SoftPlatformHooks {
+    fn modify_solver_contacts(
+        &self,
+        mut context: ContactModificationContextView,
+        user_data: &Query<StageHookData>,
+    ) {
+        let (soft1, movement1) = user_data.get(context.collider1()).unwrap_or((None, None));
+        let (soft2, movement2) = user_data.get(context.collider2()).unwrap_or((None, None));
+
+        // The normal points out of collider1, so it has to point up
+        // if the platform is collider1 and down if it is collider2
+        let (allowed_normal, movement) = match (soft1, soft2) {
+            (Some(_), None) => (Vector::y(), movement2),
+            (None, Some(_)) => (-Vector::y(), movement1),
+            _ => return,
+        };
+
+        if movement.map_or(false, |movement| movement.is_dropping_through()) {
+            // Keep ignoring the platform until the character is out of it
+            context.raw.solver_contacts.clear();
+            *context.raw.user_data = SOFT_CONTACT_FORBIDDEN;
+            return;
+        }
+
+        context
+            .raw
+            .update_as_oneway_platform(&allowed_normal, SOFT_PLATFORM_ALLOWED_ANGLE);
+    }
+}
+
+fn setup_stage(stage: Res<StageFile>, asset_server: Res<AssetServer>, audio: Res<Audio>) {
+    if let Some(music) = &stage.music {
+        audio.play_with_settings(asset_server.load(music.as_str()), PlaybackSettings::LOOP);
+    }
+}
+
+/// Sets the world's gravity from the stage's physics profile
+fn apply_physics_profile(
+    stage: Res<StageFile>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
+) {
+    if stage.is_changed() {
+        rapier_configuration.gravity = stage.physics.gravity();
+    }
+}
+
+/// Spawns the stage again whenever the stage file changes, like when it's edited
+fn respawn_stage(
+    mut commands: Commands,
+    stage: Res<StageFile>,
+    element_query: Query<Entity, With<StageElement>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !stage.is_changed() {
+        return;
+    }
+
+    for entity in element_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_stage(&mut commands, &stage, &asset_server);
+}
+
+/// Spawns everything in the stage file, all of it with `StageElement`
+fn spawn_stage(commands: &mut Commands, stage: &StageFile, asset_server: &AssetServer) {
+    for (index, layer) in stage.backgrounds.iter().enumerate() {
+        let position = layer.position.unwrap_or(stage.camera_bounds.center());
+        let z = BACKGROUND_Z + index as f32 * BACKGROUND_LAYER_SPACING;
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(layer.size.unwrap_or(stage.camera_bounds.size())),
+                    ..default()
+                },
+                texture: asset_server.load(layer.image.as_str()),
+                transform: Transform::from_translation(position.extend(z)),
+                ..default()
+            },
+            Background {
+                position,
+                parallax: layer.parallax,
+            },
+            StageElement,
+        ));
+    }
+
+    for platform in &stage.platforms {
+        let entity = spawn_platform(commands, platform);
+        commands.entity(entity).insert(StageElement);
+    }
+}
+
+/// Spawns a platform with its ledges as children, so they move along with it
+pub fn spawn_platform(commands: &mut Commands, platform: &PlatformDefinition) -> Entity {
+    let mut entity = commands.spawn((
         SpriteBundle {
             transform: Transform {
-                translation: Vec2::new(0., -1000.).extend(0.),
+                translation: platform.position.extend(0.),
                 ..default()
             },
             ..default()
         },
-        RigidBody::Fixed,
-        // GravityScale(0.),
         Velocity::default(),
-        Collider::cuboid(500., 500.),
+        platform.collider(),
         Stage,
         ActiveEvents::CONTACT_FORCE_EVENTS,
-        CollisionGroups::new(
-            Group::from_bits(0b1).unwrap(),
-            Group::from_bits(0b11).unwrap(),
-        ),
     ));
+
+    match &platform.path {
+        Some(path) => {
+            entity.insert((
+                RigidBody::KinematicPositionBased,
+                MovingPlatform {
+                    path: path.clone(),
+                    origin: platform.position,
+                    time: 0.,
+                    velocity: Vec2::ZERO,
+                },
+            ));
+        }
+        None => {
+            entity.insert(RigidBody::Fixed);
+        }
+    }
+
+    if platform.soft {
+        entity.insert((
+            SoftPlatform,
+            ActiveHooks::MODIFY_SOLVER_CONTACTS,
+            ColliderKind::SoftPlatform.collision_groups(),
+        ));
+    } else {
+        entity.insert(ColliderKind::Stage.collision_groups());
+    }
+
+    if platform.ledges {
+        entity.with_children(|children| {
+            for direction in [-1., 1.] {
+                let corner = platform.half_extents * Vec2::new(direction, 1.);
+                children.spawn((
+                    TransformBundle::from_transform(Transform::from_translation(corner.extend(0.))),
+                    Ledge { direction },
+                ));
+            }
+        });
+    }
+
+    entity.id()
+}
+
+/// Moves the background layers with the camera, the further away the more they follow it
+fn parallax_backgrounds(
+    camera_query: Query<&Transform, (With<Camera>, Without<Background>)>,
+    mut background_query: Query<(&mut Transform, &Background)>,
+) {
+    let Ok(camera_tf) = camera_query.get_single() else {
+        return;
+    };
+    let camera = camera_tf.translation.truncate();
+
+    for (mut tf, background) in background_query.iter_mut() {
+        let position = background.position + camera * background.parallax;
+        tf.translation = position.extend(tf.translation.z);
+    }
+}
+
+/// Moves the platforms along their paths by the frame's time, like the physics
+/// and every other timer, so they all stay in step
+pub fn move_platforms(
+    mut platform_query: Query<(&mut Transform, &mut MovingPlatform)>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    if delta <= 0. {
+        return;
+    }
+
+    for (mut tf, mut platform) in platform_query.iter_mut() {
+        platform.time += delta;
+        let position = platform.origin + platform.path.offset_at(platform.time);
+
+        platform.velocity = (position - tf.translation.truncate()) / delta;
+        tf.translation = position.extend(tf.translation.z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(mode: PathMode, easing: Easing) -> PlatformPath {
+        PlatformPath {
+            keyframes: vec![
+                Keyframe {
+                    time: 0.,
+                    offset: Vec2::ZERO,
+                },
+                Keyframe {
+                    time: 2.,
+                    offset: Vec2::new(100., 0.),
+                },
+            ],
+            mode,
+            easing,
+        }
+    }
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.abs_diff_eq(b, 1e-3), "{a} != {b}");
+    }
+
+    #[test]
+    fn no_keyframes_doesnt_move() {
+        let path = PlatformPath {
+            keyframes: Vec::new(),
+            ..path(PathMode::Loop, Easing::Linear)
+        };
+        assert_eq!(path.offset_at(1.), Vec2::ZERO);
+    }
+
+    #[test]
+    fn a_single_keyframe_stays_there() {
+        let mut path = path(PathMode::Loop, Easing::Linear);
+        path.keyframes.truncate(1);
+        path.keyframes[0].offset = Vec2::new(5., 5.);
+        assert_eq!(path.offset_at(3.), Vec2::new(5., 5.));
+    }
+
+    #[test]
+    fn once_stops_at_the_end() {
+        let path = path(PathMode::Once, Easing::Linear);
+        assert_close(path.offset_at(1.), Vec2::new(50., 0.));
+        assert_close(path.offset_at(2.), Vec2::new(100., 0.));
+        assert_close(path.offset_at(10.), Vec2::new(100., 0.));
+    }
+
+    #[test]
+    fn loop_starts_over() {
+        let path = path(PathMode::Loop, Easing::Linear);
+        assert_close(path.offset_at(2.5), Vec2::new(25., 0.));
+    }
+
+    #[test]
+    fn ping_pong_comes_back() {
+        let path = path(PathMode::PingPong, Easing::Linear);
+        // Both ends are reached and turned around from
+        assert_close(path.offset_at(2.), Vec2::new(100., 0.));
+        assert_close(path.offset_at(3.), Vec2::new(50., 0.));
+        assert_close(path.offset_at(4.), Vec2::ZERO);
+        assert_close(path.offset_at(5.), Vec2::new(50., 0.));
+    }
+
+    #[test]
+    fn ease_in_out_is_slower_at_the_ends() {
+        let path = path(PathMode::Once, Easing::EaseInOut);
+        assert_close(path.offset_at(1.), Vec2::new(50., 0.));
+        assert!(path.offset_at(0.2).x < 10.);
+        assert!(path.offset_at(1.8).x > 90.);
+    }
+
+    #[test]
+    fn before_the_first_keyframe_is_its_offset() {
+        let mut path = path(PathMode::Once, Easing::Linear);
+        path.keyframes[0].time = 1.;
+        assert_close(path.offset_at(0.5), Vec2::ZERO);
+    }
 }
@@ -0,0 +1,331 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::{
+    character::{CharacterReset, StatusExpired},
+    editor::EditorOptions,
+    ruleset::Ruleset,
+    stage::{stage_paths, PlatformShape, StageFile},
+    GameStates,
+};
+
+const THUMBNAIL_SIZE: egui::Vec2 = egui::Vec2::new(160., 90.);
+
+pub struct StageSelectPlugin;
+
+impl Plugin for StageSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(load_stages)
+            .add_startup_system(stage_select_setup)
+            .add_plugin(InputManagerPlugin::<StageSelectActions>::default())
+            .add_system(stage_select_ui.in_set(OnUpdate(GameStates::StageSelect)))
+            .add_system(back_to_stage_select.in_set(OnUpdate(GameStates::Playing)))
+            .add_system(pause_physics.in_schedule(OnEnter(GameStates::StageSelect)))
+            .add_systems(
+                (resume_physics, reset_characters).in_schedule(OnExit(GameStates::StageSelect)),
+            );
+    }
+}
+
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug)]
+enum StageSelectActions {
+    /// Ends the game and goes back to pick the stage of the next one
+    Back,
+}
+
+/// Every stage in `assets/stages`, and which ones were struck or banned
+#[derive(Resource)]
+struct StageSelect {
+    stages: Vec<StageEntry>,
+    counterpick: bool,
+    /// Indices into `stages`
+    struck: Vec<usize>,
+}
+
+struct StageEntry {
+    /// The file name without the extension, what the ruleset's stage lists use
+    id: String,
+    file: StageFile,
+    thumbnail: Option<Handle<Image>>,
+}
+
+impl StageSelect {
+    /// The stages that can be picked right now, by index into `stages`
+    fn legal(&self, ruleset: &Ruleset) -> Vec<usize> {
+        let list = &ruleset.stages;
+        (0..self.stages.len())
+            .filter(|index| {
+                let id = &self.stages[*index].id;
+                list.is_starter(id) || (self.counterpick && list.is_counterpick(id))
+            })
+            .collect()
+    }
+
+    /// Whether clicking a stage strikes or bans it instead of picking it
+    fn is_striking(&self, ruleset: &Ruleset) -> bool {
+        if self.counterpick {
+            self.struck.len() < ruleset.stages.bans
+        } else {
+            ruleset.stages.strikes
+        }
+    }
+
+    /// Who strikes next, starting with 0. The first player strikes one,
+    /// then they take turns striking two
+    fn striking_player(&self) -> usize {
+        match self.struck.len() {
+            0 => 0,
+            struck => ((struck - 1) / 2 + 1) % 2,
+        }
+    }
+}
+
+fn stage_select_setup(mut commands: Commands) {
+    commands.spawn(InputManagerBundle::<StageSelectActions> {
+        input_map: InputMap::new([(KeyCode::Back, StageSelectActions::Back)])
+            .insert(GamepadButtonType::Select, StageSelectActions::Back)
+            .build(),
+        ..default()
+    });
+}
+
+/// Stages that can't be read are left out, with a warning
+fn load_stages(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let stages = stage_paths()
+        .iter()
+        .filter_map(|path| {
+            let file = match StageFile::load(&path.to_string_lossy()) {
+                Ok(file) => file,
+                Err(error) => {
+                    warn!("Leaving the stage out: {error}");
+                    return None;
+                }
+            };
+            Some(StageEntry {
+                id: path.file_stem()?.to_string_lossy().to_string(),
+                thumbnail: file
+                    .thumbnail
+                    .as_ref()
+                    .map(|thumbnail| asset_server.load(thumbnail.as_str())),
+                file,
+            })
+        })
+        .collect();
+
+    commands.insert_resource(StageSelect {
+        stages,
+        counterpick: false,
+        struck: Vec::new(),
+    });
+}
+
+/// After the first game, the next ones are counterpicks
+fn back_to_stage_select(
+    action_state_query: Query<&ActionState<StageSelectActions>>,
+    mut select: ResMut<StageSelect>,
+    mut next_state: ResMut<NextState<GameStates>>,
+) {
+    let action_state = action_state_query.single();
+
+    if action_state.just_pressed(StageSelectActions::Back) {
+        select.counterpick = true;
+        select.struck.clear();
+        next_state.set(GameStates::StageSelect);
+    }
+}
+
+fn pause_physics(mut rapier_configuration: ResMut<RapierConfiguration>) {
+    rapier_configuration.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier_configuration: ResMut<RapierConfiguration>) {
+    rapier_configuration.physics_pipeline_active = true;
+}
+
+fn stage_select_ui(
+    mut contexts: EguiContexts,
+    mut select: ResMut<StageSelect>,
+    mut stage: ResMut<StageFile>,
+    mut next_state: ResMut<NextState<GameStates>>,
+    ruleset: Res<Ruleset>,
+) {
+    let thumbnails: Vec<_> = select
+        .stages
+        .iter()
+        .map(|entry| {
+            entry
+                .thumbnail
+                .as_ref()
+                .map(|thumbnail| contexts.add_image(thumbnail.clone_weak()))
+        })
+        .collect();
+
+    let legal = select.legal(&ruleset);
+    let left: Vec<usize> = legal
+        .iter()
+        .copied()
+        .filter(|index| !select.struck.contains(index))
+        .collect();
+    let mut picked = None;
+
+    egui::CentralPanel::default().show(contexts.ctx_mut(), |ui| {
+        ui.heading("Stage Select");
+
+        ui.horizontal(|ui| {
+            let mut counterpick = select.counterpick;
+            ui.radio_value(&mut counterpick, false, "First game");
+            ui.radio_value(&mut counterpick, true, "Counterpick");
+            if counterpick != select.counterpick {
+                select.counterpick = counterpick;
+                select.struck.clear();
+            }
+
+            if ui.button("Random").clicked() && !left.is_empty() {
+                // Good enough randomness for picking a stage
+                let nanos = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .subsec_nanos() as usize;
+                picked = Some(left[nanos % left.len()]);
+            }
+            if ui.button("Reset").clicked() {
+                select.struck.clear();
+            }
+        });
+
+        let striking = select.is_striking(&ruleset) && left.len() > 1;
+        if striking && select.counterpick {
+            ui.label("The winner bans a stage");
+        } else if striking {
+            ui.label(format!(
+                "Player {} strikes a stage",
+                select.striking_player() + 1
+            ));
+        } else {
+            ui.label("Pick a stage");
+        }
+        ui.separator();
+
+        ui.horizontal_wrapped(|ui| {
+            for index in legal.iter().copied() {
+                let entry = &select.stages[index];
+                let struck = select.struck.contains(&index);
+
+                ui.vertical(|ui| {
+                    ui.add_enabled_ui(!struck, |ui| {
+                        if let Some(thumbnail) = thumbnails[index] {
+                            ui.image(thumbnail, THUMBNAIL_SIZE);
+                        }
+                        platform_overview(ui, &entry.file);
+
+                        let label = if struck {
+                            format!("{} (struck)", entry.file.name)
+                        } else {
+                            entry.file.name.clone()
+                        };
+                        if ui.button(label).clicked() {
+                            if striking {
+                                select.struck.push(index);
+                            } else {
+                                picked = Some(index);
+                            }
+                        }
+                    });
+                });
+            }
+        });
+    });
+
+    // Striking until one is left picks it
+    if !select.counterpick && ruleset.stages.strikes && left.len() == 1 {
+        picked = Some(left[0]);
+    }
+
+    let Some(picked) = picked else {
+        return;
+    };
+
+    let entry = &select.stages[picked];
+    *stage = entry.file.clone();
+    select.struck.clear();
+    next_state.set(GameStates::Playing);
+}
+
+/// The picked stage starts a new game, everyone starts over at its spawn points.
+/// The editor's selection was of the old stage's platforms
+fn reset_characters(
+    mut character_query: Query<CharacterReset>,
+    stage: Res<StageFile>,
+    mut editor_options: ResMut<EditorOptions>,
+    mut commands: Commands,
+    mut expired_events: EventWriter<StatusExpired>,
+) {
+    editor_options.clear_stage_selection();
+
+    for mut character in character_query.iter_mut() {
+        let spawn_point = stage.spawn_point(character.character.slot);
+        character.reset(spawn_point, &mut commands, &mut expired_events);
+    }
+}
+
+/// A small drawing of the stage's platforms and spawn points inside its blast zone
+fn platform_overview(ui: &mut egui::Ui, stage: &StageFile) {
+    let (response, painter) = ui.allocate_painter(THUMBNAIL_SIZE, egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 0., egui::Color32::from_gray(30));
+
+    let zone = stage.blast_zone;
+    let to_screen = |point: Vec2| {
+        let t = (point - zone.min) / zone.size();
+        egui::pos2(
+            rect.left() + t.x * rect.width(),
+            rect.bottom() - t.y * rect.height(),
+        )
+    };
+
+    for platform in &stage.platforms {
+        let color = if platform.soft {
+            egui::Color32::GRAY
+        } else {
+            egui::Color32::WHITE
+        };
+        match &platform.shape {
+            PlatformShape::Box => {
+                painter.rect_filled(
+                    egui::Rect::from_two_pos(
+                        to_screen(platform.position - platform.half_extents),
+                        to_screen(platform.position + platform.half_extents),
+                    ),
+                    0.,
+                    color,
+                );
+            }
+            PlatformShape::Polygon(points) => {
+                painter.add(egui::Shape::convex_polygon(
+                    points
+                        .iter()
+                        .map(|point| to_screen(platform.position + *point))
+                        .collect(),
+                    color,
+                    egui::Stroke::NONE,
+                ));
+            }
+            PlatformShape::Polyline { .. } => {
+                for points in platform.shape.loops() {
+                    painter.add(egui::Shape::closed_line(
+                        points
+                            .iter()
+                            .map(|point| to_screen(platform.position + *point))
+                            .collect(),
+                        egui::Stroke::new(1., color),
+                    ));
+                }
+            }
+        }
+    }
+    for point in &stage.spawn_points {
+        painter.circle_filled(to_screen(*point), 2., egui::Color32::GREEN);
+    }
+}
@@ -3,8 +3,13 @@ use bevy_egui::{egui, EguiContexts};
 use serde_json::{json, Value};
 
 use crate::{
-    character::{Character, CharacterMovement},
+    character::{Character, CharacterMovement, MovementAbilities, Moveset, StaleMoves},
     editor::EditorOptions,
+    import::import_stage,
+    layers::ColliderKind,
+    mask::mask_platforms,
+    ruleset::Ruleset,
+    stage::StageFile,
     GameStates,
 };
 
@@ -13,14 +18,26 @@ pub struct UiPlugin;
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(editor_ui.in_set(OnUpdate(GameStates::Editor)))
+            .add_system(collision_layers_ui.in_set(OnUpdate(GameStates::Editor)))
             .add_system(show_percentage.in_set(OnUpdate(GameStates::Playing)));
     }
 }
 
-fn show_percentage(mut contexts: EguiContexts, character: Query<(&Name, &Character, &Transform)>) {
+fn show_percentage(
+    mut contexts: EguiContexts,
+    character: Query<(&Name, &Character, &StaleMoves)>,
+    ruleset: Res<Ruleset>,
+) {
     egui::Window::new("Percetage").show(contexts.ctx_mut(), |ui| {
         for character in character.iter() {
-            ui.label(format!("{}: {}%", character.0, character.1.percentage,));
+            ui.label(format!("{}: {:.1}%", character.0, character.1.percentage,));
+
+            // Training info
+            ui.indent(character.0.as_str(), |ui| {
+                for (kind, multiplier) in character.2.staleness(&ruleset.stale_moves) {
+                    ui.label(format!("{kind:?}: {:.0}%", multiplier * 100.));
+                }
+            });
         }
     });
 }
@@ -28,7 +45,18 @@ fn show_percentage(mut contexts: EguiContexts, character: Query<(&Name, &Charact
 fn editor_ui(
     mut context: EguiContexts,
     mut options: ResMut<EditorOptions>,
-    mut query: Query<(&mut CharacterMovement, &mut Name), With<Character>>,
+    mut query: Query<
+        (
+            &mut CharacterMovement,
+            &mut MovementAbilities,
+            &Moveset,
+            &StaleMoves,
+            &mut Name,
+        ),
+        With<Character>,
+    >,
+    ruleset: Res<Ruleset>,
+    mut stage: ResMut<StageFile>,
 ) {
     let ctx = context.ctx_mut();
 
@@ -38,11 +66,29 @@ fn editor_ui(
     egui::SidePanel::left("side_panel")
         .default_width(200.0)
         .show(ctx, |ui| {
+            let mut editing_stage = options.editing_stage;
+            ui.checkbox(&mut editing_stage, "Edit stage");
+            if editing_stage != options.editing_stage {
+                options.editing_stage = editing_stage;
+                options.editing_character = None;
+            }
+
+            if options.editing_stage {
+                // Only touch the options when something changed, so the markers aren't
+                // respawned every frame
+                let mut edited = options.clone();
+                stage_editor_ui(ui, &mut edited, &mut stage);
+                if *options != edited {
+                    *options = edited;
+                }
+                return;
+            }
+
             if options.editing_character.is_none() {
                 ui.heading("Click a character to edit");
                 return;
             }
-            let (mut movement, mut name) =
+            let (mut movement, mut abilities, moveset, stale_moves, mut name) =
                 query.get_mut(options.editing_character.unwrap()).unwrap();
 
             ui.heading(format!("Editing: {}", *name));
@@ -139,6 +185,79 @@ fn editor_ui(
                 ui.checkbox(&mut movement.can_walljump, "Can Walljump");
             });
 
+            ui.separator();
+            ui.heading("Abilities");
+
+            ability_editor(ui, "Float", &mut abilities.float, |ui, float| {
+                ui.horizontal(|ui| {
+                    ui.label("duration: ");
+                    ui.add(egui::Slider::new(&mut float.duration, 0.0..=10.));
+                });
+            });
+
+            ability_editor(ui, "Glide", &mut abilities.glide, |ui, glide| {
+                ui.horizontal(|ui| {
+                    ui.label("fall_speed: ");
+                    ui.add(egui::Slider::new(&mut glide.fall_speed, 0.0..=f32::MAX));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("speed: ");
+                    ui.add(egui::Slider::new(&mut glide.speed, 0.0..=f32::MAX));
+                });
+            });
+
+            ability_editor(
+                ui,
+                "Multi Jump",
+                &mut abilities.multi_jump,
+                |ui, multi_jump| {
+                    ui.horizontal(|ui| {
+                        ui.label("jumps: ");
+                        ui.add(egui::Slider::new(&mut multi_jump.jumps, 0..=10));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("decay: ");
+                        ui.add(egui::Slider::new(&mut multi_jump.decay, 0.0..=1.));
+                    });
+                },
+            );
+
+            ability_editor(
+                ui,
+                "Wall Cling",
+                &mut abilities.wall_cling,
+                |ui, wall_cling| {
+                    ui.horizontal(|ui| {
+                        ui.label("duration: ");
+                        ui.add(egui::Slider::new(&mut wall_cling.duration, 0.0..=10.));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("slide_speed: ");
+                        ui.add(egui::Slider::new(
+                            &mut wall_cling.slide_speed,
+                            0.0..=f32::MAX,
+                        ));
+                    });
+                },
+            );
+
+            ability_editor(ui, "Crawl", &mut abilities.crawl, |ui, crawl| {
+                ui.horizontal(|ui| {
+                    ui.label("speed: ");
+                    ui.add(egui::Slider::new(&mut crawl.speed, 0.0..=f32::MAX));
+                });
+            });
+
+            ui.separator();
+            ui.heading("Staleness");
+
+            let mut kinds: Vec<_> = moveset.0.keys().collect();
+            kinds.sort_by_key(|kind| format!("{kind:?}"));
+            for kind in kinds {
+                let multiplier = stale_moves.multiplier(*kind, &ruleset.stale_moves);
+                ui.label(format!("{kind:?}: {:.0}%", multiplier * 100.));
+            }
+
             ui.allocate_space(egui::Vec2::new(1.0, 100.0));
 
             ui.horizontal(|ui| {
@@ -153,3 +272,170 @@ fn editor_ui(
             });
         });
 }
+
+/// Which kinds of colliders interact with which, as a table
+fn collision_layers_ui(mut contexts: EguiContexts) {
+    egui::Window::new("Collision layers")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("collision_layers").show(ui, |ui| {
+                ui.label("");
+                for kind in ColliderKind::ALL {
+                    ui.label(format!("{kind:?}"));
+                }
+                ui.end_row();
+
+                for kind in ColliderKind::ALL {
+                    ui.label(format!("{kind:?}"));
+                    for other in ColliderKind::ALL {
+                        ui.label(if kind.interacts_with(other) { "x" } else { "" });
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+/// The stage editor's panel, dragging things around is done by `editor::stage_editor`
+fn stage_editor_ui(ui: &mut egui::Ui, options: &mut EditorOptions, stage: &mut ResMut<StageFile>) {
+    ui.heading(format!("Editing: {}", stage.name));
+    ui.label("Drag platforms, their top right corner, spawn points and blast zone edges");
+    ui.label("Right click: place a platform");
+    ui.label("Delete: remove the selected platform");
+
+    ui.horizontal(|ui| {
+        ui.label("Grid: ");
+        ui.add(egui::Slider::new(&mut options.grid, 0.0..=200.));
+    });
+
+    let selected = options
+        .selected_platform
+        .and_then(|index| Some((index, stage.platforms.get(index)?)));
+    if let Some((index, platform)) = selected {
+        ui.separator();
+        ui.heading(format!("Platform {index}"));
+
+        // Only touch the stage when something changed, so it isn't respawned every frame
+        let (mut soft, mut ledges) = (platform.soft, platform.ledges);
+        ui.checkbox(&mut soft, "Soft platform");
+        ui.checkbox(&mut ledges, "Ledges");
+        if soft != platform.soft || ledges != platform.ledges {
+            if let Some(platform) = stage.platforms.get_mut(index) {
+                platform.soft = soft;
+                platform.ledges = ledges;
+            }
+        }
+    }
+
+    ui.separator();
+    ui.heading("Physics");
+    let mut physics = stage.physics.clone();
+    ui.horizontal(|ui| {
+        ui.label("Gravity multiplier: ");
+        ui.add(egui::Slider::new(&mut physics.gravity_multiplier, 0.0..=3.));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Pixels per unit: ");
+        ui.add(egui::Slider::new(&mut physics.pixels_per_unit, 0.1..=10.));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Air friction: ");
+        ui.add(egui::Slider::new(&mut physics.air_friction, 0.0..=10.));
+    });
+    if physics.gravity_multiplier != stage.physics.gravity_multiplier
+        || physics.pixels_per_unit != stage.physics.pixels_per_unit
+        || physics.air_friction != stage.physics.air_friction
+    {
+        stage.physics = physics;
+    }
+
+    ui.separator();
+    ui.heading("Import");
+    ui.label("A Tiled (.tmx, .tmj) or LDtk (.ldtk) map, it replaces the stage");
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut options.import_path);
+        if ui.button("Import").clicked() {
+            match import_stage(&options.import_path) {
+                Ok(imported) => {
+                    let path = stage.path.clone();
+                    **stage = StageFile { path, ..imported };
+                    options.clear_stage_selection();
+                    options.stage_message = Some(format!("Imported {}", options.import_path));
+                }
+                Err(error) => options.stage_message = Some(error),
+            }
+        }
+    });
+
+    ui.separator();
+    ui.heading("Colliders from sprite");
+    ui.label("Adds platforms outlining the opaque parts of a sprite and saves the stage");
+    ui.horizontal(|ui| {
+        ui.label("Sprite: ");
+        ui.text_edit_singleline(&mut options.mask.sprite);
+    });
+    ui.horizontal(|ui| {
+        ui.label("Alpha cutoff: ");
+        ui.add(egui::Slider::new(&mut options.mask.alpha_cutoff, 0.0..=1.));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Tolerance: ");
+        ui.add(egui::Slider::new(&mut options.mask.tolerance, 0.0..=10.));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Scale: ");
+        ui.add(egui::Slider::new(&mut options.mask.scale, 0.1..=10.));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Position: ");
+        ui.add(egui::DragValue::new(&mut options.mask.position.x));
+        ui.add(egui::DragValue::new(&mut options.mask.position.y));
+    });
+    if ui.button("Generate").clicked() {
+        match mask_platforms(&options.mask) {
+            Ok(platforms) => {
+                stage.platforms.extend(platforms);
+                options.clear_stage_selection();
+                options.stage_message = Some(save_message(stage));
+            }
+            Err(error) => options.stage_message = Some(error),
+        }
+    }
+
+    ui.allocate_space(egui::Vec2::new(1.0, 100.0));
+
+    if ui.button("Save Stage to File").clicked() {
+        options.stage_message = Some(save_message(stage));
+    }
+    if let Some(message) = &options.stage_message {
+        ui.label(message);
+    }
+}
+
+fn save_message(stage: &StageFile) -> String {
+    match stage.save() {
+        Ok(()) => format!("Saved to {}", stage.path),
+        Err(error) => error,
+    }
+}
+
+/// A checkbox to give the character the ability, with its tunables under it
+fn ability_editor<T: Default>(
+    ui: &mut egui::Ui,
+    name: &str,
+    ability: &mut Option<T>,
+    tunables: impl FnOnce(&mut egui::Ui, &mut T),
+) {
+    let mut has_ability = ability.is_some();
+    ui.checkbox(&mut has_ability, name);
+
+    match (has_ability, ability.is_some()) {
+        (true, false) => *ability = Some(T::default()),
+        (false, true) => *ability = None,
+        _ => {}
+    }
+
+    if let Some(ability) = ability {
+        ui.indent(name, |ui| tunables(ui, ability));
+    }
+}